@@ -0,0 +1,430 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+use windows::Foundation::{DateTime, EventRegistrationToken, TypedEventHandler};
+use windows::Media::Control::GlobalSystemMediaTransportControlsSession as GSMTCSession;
+use windows::Media::Control::GlobalSystemMediaTransportControlsSessionManager as GSMTCSessionManager;
+use windows::Media::Control::GlobalSystemMediaTransportControlsSessionPlaybackControls as GSMTCPlaybackControls;
+use windows::Media::MediaPlaybackAutoRepeatMode;
+use windows::Storage::Streams::DataReader;
+
+use super::{MediaBackend, MediaEvent, MediaSession};
+use crate::control::PlaybackCommand;
+use crate::snapshot::{
+    FieldErrors, MediaProperties, PlatformError, PlaybackControls, PlaybackInfo, RepeatMode,
+    Thumbnail, TimelineProperties,
+};
+use crate::thumbnail::ThumbnailData;
+
+pub struct GsmtcBackend {
+    session_manager: GSMTCSessionManager,
+}
+
+impl GsmtcBackend {
+    pub async fn new() -> Result<Self> {
+        let session_manager = GSMTCSessionManager::RequestAsync()?.await?;
+        Ok(Self { session_manager })
+    }
+}
+
+#[async_trait]
+impl MediaBackend for GsmtcBackend {
+    async fn sessions(&self) -> Result<Vec<Box<dyn MediaSession>>> {
+        let mut sessions: Vec<Box<dyn MediaSession>> = vec![];
+        for session in self.session_manager.GetSessions()? {
+            sessions.push(Box::new(GsmtcSession::new(session)?));
+        }
+        Ok(sessions)
+    }
+
+    async fn current_session(&self) -> Result<Option<Box<dyn MediaSession>>> {
+        // GetCurrentSession reports "no session" as an error wrapping a null object.
+        match self.session_manager.GetCurrentSession() {
+            Ok(session) => Ok(Some(Box::new(GsmtcSession::new(session)?))),
+            Err(_) => Ok(None),
+        }
+    }
+
+    async fn watch(&self) -> Result<UnboundedReceiver<MediaEvent>> {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let watcher = Arc::new(Watcher {
+            tx,
+            sessions: Mutex::new(HashMap::new()),
+        });
+        watcher.sync_sessions(&self.session_manager, false)?;
+
+        let sessions_watcher = watcher.clone();
+        let sessions_changed = self
+            .session_manager
+            .SessionsChanged(&TypedEventHandler::new(
+                move |manager: &Option<GSMTCSessionManager>, _| {
+                    if let Some(manager) = manager {
+                        sessions_watcher.sync_sessions(manager, true)?;
+                    }
+                    Ok(())
+                },
+            ))?;
+
+        let tx = watcher.tx.clone();
+        let current_changed =
+            self.session_manager
+                .CurrentSessionChanged(&TypedEventHandler::new(
+                    move |manager: &Option<GSMTCSessionManager>, _| {
+                        let session = manager
+                            .as_ref()
+                            .and_then(|manager| manager.GetCurrentSession().ok())
+                            .and_then(|session| session.SourceAppUserModelId().ok())
+                            .map(|id| id.to_string());
+                        let _ = tx.send(MediaEvent::CurrentSessionChanged { session });
+                        Ok(())
+                    },
+                ))?;
+
+        // Handlers keep the watcher alive, so take them all down once the
+        // receiver is dropped.
+        let manager = self.session_manager.clone();
+        tokio::spawn(async move {
+            watcher.tx.closed().await;
+            let _ = manager.RemoveSessionsChanged(sessions_changed);
+            let _ = manager.RemoveCurrentSessionChanged(current_changed);
+            watcher.detach_all();
+        });
+
+        Ok(rx)
+    }
+}
+
+/// Keeps change handlers attached to every session the manager reports.
+struct Watcher {
+    tx: UnboundedSender<MediaEvent>,
+    sessions: Mutex<HashMap<String, (GSMTCSession, [EventRegistrationToken; 3])>>,
+}
+
+impl Watcher {
+    /// Attaches handlers to new sessions and detaches them from sessions that
+    /// went away, reporting both when `notify` is set.
+    fn sync_sessions(
+        &self,
+        manager: &GSMTCSessionManager,
+        notify: bool,
+    ) -> windows::core::Result<()> {
+        if self.tx.is_closed() {
+            return Ok(());
+        }
+        let mut current = HashMap::new();
+        for session in manager.GetSessions()? {
+            current.insert(session.SourceAppUserModelId()?.to_string(), session);
+        }
+
+        let mut sessions = self.sessions.lock().unwrap();
+        let removed = sessions
+            .keys()
+            .filter(|id| !current.contains_key(*id))
+            .cloned()
+            .collect::<Vec<_>>();
+        for id in removed {
+            if let Some((session, tokens)) = sessions.remove(&id) {
+                detach(&session, tokens);
+            }
+            if notify {
+                let _ = self.tx.send(MediaEvent::SessionRemoved { session: id });
+            }
+        }
+
+        for (id, session) in current {
+            if sessions.contains_key(&id) {
+                continue;
+            }
+            let tokens = self.attach(&id, &session)?;
+            sessions.insert(id.clone(), (session, tokens));
+            if notify {
+                let _ = self.tx.send(MediaEvent::SessionAdded { session: id });
+            }
+        }
+        Ok(())
+    }
+
+    /// Detaches the handlers of every session.
+    fn detach_all(&self) {
+        for (_, (session, tokens)) in self.sessions.lock().unwrap().drain() {
+            detach(&session, tokens);
+        }
+    }
+
+    fn attach(
+        &self,
+        id: &str,
+        session: &GSMTCSession,
+    ) -> windows::core::Result<[EventRegistrationToken; 3]> {
+        let (tx, session_id) = (self.tx.clone(), id.to_string());
+        let media = session.MediaPropertiesChanged(&TypedEventHandler::new(move |_, _| {
+            let session = session_id.clone();
+            let _ = tx.send(MediaEvent::MediaPropertiesChanged { session });
+            Ok(())
+        }))?;
+
+        let (tx, session_id) = (self.tx.clone(), id.to_string());
+        let playback = session.PlaybackInfoChanged(&TypedEventHandler::new(move |_, _| {
+            let session = session_id.clone();
+            let _ = tx.send(MediaEvent::PlaybackInfoChanged { session });
+            Ok(())
+        }))?;
+
+        let (tx, session_id) = (self.tx.clone(), id.to_string());
+        let timeline =
+            session.TimelinePropertiesChanged(&TypedEventHandler::new(move |_, _| {
+                let session = session_id.clone();
+                let _ = tx.send(MediaEvent::TimelinePropertiesChanged { session });
+                Ok(())
+            }))?;
+
+        Ok([media, playback, timeline])
+    }
+}
+
+fn detach(session: &GSMTCSession, [media, playback, timeline]: [EventRegistrationToken; 3]) {
+    let _ = session.RemoveMediaPropertiesChanged(media);
+    let _ = session.RemovePlaybackInfoChanged(playback);
+    let _ = session.RemoveTimelinePropertiesChanged(timeline);
+}
+
+pub struct GsmtcSession {
+    session: GSMTCSession,
+    app_user_model_id: String,
+}
+
+impl GsmtcSession {
+    fn new(session: GSMTCSession) -> Result<Self> {
+        let app_user_model_id = session.SourceAppUserModelId()?.to_string();
+        Ok(Self {
+            session,
+            app_user_model_id,
+        })
+    }
+}
+
+#[async_trait]
+impl MediaSession for GsmtcSession {
+    fn id(&self) -> &str {
+        &self.app_user_model_id
+    }
+
+    async fn media_properties(&self, errors: &mut FieldErrors) -> Option<MediaProperties> {
+        let open_properties = errors.section(self.session.TryGetMediaPropertiesAsync())?;
+        let media_properties = errors.section(open_properties.await)?;
+
+        // Keep the stream reference out of the await so the future stays Send.
+        // A missing one means there is no cover art, as in `thumbnail`.
+        let open_thumbnail = match media_properties.Thumbnail() {
+            Ok(thumbnail) => errors.read("thumbnail", thumbnail.OpenReadAsync()),
+            Err(_) => None,
+        };
+        let thumbnail = match open_thumbnail {
+            Some(open_thumbnail) => errors.read("thumbnail", open_thumbnail.await),
+            None => None,
+        };
+
+        Some(MediaProperties {
+            album_artist: errors
+                .read("album_artist", media_properties.AlbumArtist())
+                .map(|v| v.to_string()),
+            album_title: errors
+                .read("album_title", media_properties.AlbumTitle())
+                .map(|v| v.to_string()),
+            album_track_count: errors.read("album_track_count", media_properties.AlbumTrackCount()),
+            artist: errors
+                .read("artist", media_properties.Artist())
+                .map(|v| v.to_string()),
+            genres: errors
+                .read("genres", media_properties.Genres())
+                .map(|genres| genres.into_iter().map(|genre| genre.to_string()).collect())
+                .unwrap_or_default(),
+            playback_type: errors
+                .read(
+                    "playback_type",
+                    media_properties.PlaybackType().and_then(|v| v.Value()),
+                )
+                .map(|v| v.0.into()),
+            subtitle: errors
+                .read("subtitle", media_properties.Subtitle())
+                .map(|v| v.to_string()),
+            thumbnail: thumbnail.map(|thumbnail| Thumbnail {
+                content_type: errors
+                    .read("thumbnail.content_type", thumbnail.ContentType())
+                    .map(|v| v.to_string()),
+                size: errors.read("thumbnail.size", thumbnail.Size()),
+                ..Default::default()
+            }),
+            title: errors
+                .read("title", media_properties.Title())
+                .map(|v| v.to_string()),
+            track_number: errors.read("track_number", media_properties.TrackNumber()),
+        })
+    }
+
+    async fn thumbnail(&self) -> Result<Option<ThumbnailData>> {
+        let open_properties = self.session.TryGetMediaPropertiesAsync()?;
+        let media_properties = open_properties.await?;
+        // Sessions without cover art have no stream reference to open.
+        let open_thumbnail = match media_properties.Thumbnail() {
+            Ok(thumbnail) => thumbnail.OpenReadAsync()?,
+            Err(_) => return Ok(None),
+        };
+        // The stream is not Send, so only the reader outlives the next await.
+        let (content_type, size, reader) = {
+            let stream = open_thumbnail.await?;
+            let size = u32::try_from(stream.Size()?)?;
+            let reader = DataReader::CreateDataReader(&stream)?;
+            (stream.ContentType()?.to_string(), size, reader)
+        };
+        let load = reader.LoadAsync(size)?;
+        load.await?;
+        let mut bytes = vec![0; size as usize];
+        reader.ReadBytes(&mut bytes)?;
+
+        Ok(Some(ThumbnailData {
+            content_type: Some(content_type),
+            bytes,
+        }))
+    }
+
+    async fn playback_info(&self, errors: &mut FieldErrors) -> Option<PlaybackInfo> {
+        let playback_info = errors.section(self.session.GetPlaybackInfo())?;
+
+        Some(PlaybackInfo {
+            auto_repeat_mode: errors
+                .read(
+                    "auto_repeat_mode",
+                    playback_info.AutoRepeatMode().and_then(|v| v.Value()),
+                )
+                .map(|v| v.0.into()),
+            controls: errors
+                .read("controls", playback_info.Controls())
+                .map(|controls| playback_controls(&controls, errors)),
+            is_shuffle_active: errors.read(
+                "is_shuffle_active",
+                playback_info.IsShuffleActive().and_then(|v| v.Value()),
+            ),
+            playback_rate: errors.read(
+                "playback_rate",
+                playback_info.PlaybackRate().and_then(|v| v.Value()),
+            ),
+            playback_status: errors
+                .read("playback_status", playback_info.PlaybackStatus())
+                .map(|v| v.0.into()),
+            playback_type: errors
+                .read(
+                    "playback_type",
+                    playback_info.PlaybackType().and_then(|v| v.Value()),
+                )
+                .map(|v| v.0.into()),
+        })
+    }
+
+    async fn timeline_properties(&self, errors: &mut FieldErrors) -> Option<TimelineProperties> {
+        let timeline_properties = errors.section(self.session.GetTimelineProperties())?;
+
+        Some(TimelineProperties {
+            start_time: errors
+                .read("start_time", timeline_properties.StartTime())
+                .map(Duration::from),
+            end_time: errors
+                .read("end_time", timeline_properties.EndTime())
+                .map(Duration::from),
+            max_seek_time: errors
+                .read("max_seek_time", timeline_properties.MaxSeekTime())
+                .map(Duration::from),
+            min_seek_time: errors
+                .read("min_seek_time", timeline_properties.MinSeekTime())
+                .map(Duration::from),
+            position: errors
+                .read("position", timeline_properties.Position())
+                .map(Duration::from),
+            last_updated_time: errors
+                .read("last_updated_time", timeline_properties.LastUpdatedTime())
+                .map(|time: DateTime| time.UniversalTime),
+        })
+    }
+
+    async fn send_command(&self, command: PlaybackCommand) -> Result<bool> {
+        let request = match command {
+            PlaybackCommand::Play => self.session.TryPlayAsync()?,
+            PlaybackCommand::Pause => self.session.TryPauseAsync()?,
+            PlaybackCommand::TogglePlayPause => self.session.TryTogglePlayPauseAsync()?,
+            PlaybackCommand::Next => self.session.TrySkipNextAsync()?,
+            PlaybackCommand::Previous => self.session.TrySkipPreviousAsync()?,
+            PlaybackCommand::Stop => self.session.TryStopAsync()?,
+        };
+        Ok(request.await?)
+    }
+
+    async fn set_position(&self, position: Duration) -> Result<bool> {
+        let ticks = (position.as_nanos() / 100) as i64;
+        let request = self.session.TryChangePlaybackPositionAsync(ticks)?;
+        Ok(request.await?)
+    }
+
+    async fn set_repeat_mode(&self, mode: RepeatMode) -> Result<bool> {
+        let mode = MediaPlaybackAutoRepeatMode(mode.into());
+        let request = self.session.TryChangeAutoRepeatModeAsync(mode)?;
+        Ok(request.await?)
+    }
+
+    async fn set_shuffle(&self, active: bool) -> Result<bool> {
+        let request = self.session.TryChangeShuffleActiveAsync(active)?;
+        Ok(request.await?)
+    }
+
+    async fn set_playback_rate(&self, rate: f64) -> Result<bool> {
+        let request = self.session.TryChangePlaybackRateAsync(rate)?;
+        Ok(request.await?)
+    }
+}
+
+fn playback_controls(
+    controls: &GSMTCPlaybackControls,
+    errors: &mut FieldErrors,
+) -> PlaybackControls {
+    let mut read = |field: &str, value| errors.read(&format!("controls.{field}"), value);
+
+    PlaybackControls {
+        is_channel_down_enabled: read("is_channel_down_enabled", controls.IsChannelDownEnabled()),
+        is_channel_up_enabled: read("is_channel_up_enabled", controls.IsChannelUpEnabled()),
+        is_fast_forward_enabled: read("is_fast_forward_enabled", controls.IsFastForwardEnabled()),
+        is_next_enabled: read("is_next_enabled", controls.IsNextEnabled()),
+        is_pause_enabled: read("is_pause_enabled", controls.IsPauseEnabled()),
+        is_playback_position_enabled: read(
+            "is_playback_position_enabled",
+            controls.IsPlaybackPositionEnabled(),
+        ),
+        is_playback_rate_enabled: read(
+            "is_playback_rate_enabled",
+            controls.IsPlaybackRateEnabled(),
+        ),
+        is_play_enabled: read("is_play_enabled", controls.IsPlayEnabled()),
+        is_play_pause_toggle_enabled: read(
+            "is_play_pause_toggle_enabled",
+            controls.IsPlayPauseToggleEnabled(),
+        ),
+        is_previous_enabled: read("is_previous_enabled", controls.IsPreviousEnabled()),
+        is_record_enabled: read("is_record_enabled", controls.IsRecordEnabled()),
+        is_repeat_enabled: read("is_repeat_enabled", controls.IsRepeatEnabled()),
+        is_rewind_enabled: read("is_rewind_enabled", controls.IsRewindEnabled()),
+        is_shuffle_enabled: read("is_shuffle_enabled", controls.IsShuffleEnabled()),
+        is_stop_enabled: read("is_stop_enabled", controls.IsStopEnabled()),
+    }
+}
+
+impl PlatformError for windows::core::Error {
+    fn code(&self) -> Option<String> {
+        Some(format!("{:#010X}", self.code().0))
+    }
+
+    fn message(&self) -> String {
+        windows::core::Error::message(self)
+    }
+}
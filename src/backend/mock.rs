@@ -0,0 +1,792 @@
+//! An in-memory backend for tests and demos.
+//!
+//! Sessions are plain [`SessionSnapshot`]s, scripted from Rust through
+//! [`MockBackend`] or loaded from a file in the shape `--all` prints. Every
+//! mutation, including the commands the sessions receive, is reported to
+//! watchers like a platform would.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, SystemTime};
+
+use anyhow::{anyhow, bail, Context, Result};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+
+use super::{universal_time, MediaBackend, MediaEvent, MediaSession};
+use crate::control::PlaybackCommand;
+use crate::duration;
+use crate::position::PositionEstimator;
+use crate::snapshot::{
+    FieldErrors, MediaProperties, PlaybackInfo, PlaybackStatus, RepeatMode, SessionSnapshot,
+    TimelineProperties,
+};
+use crate::thumbnail::ThumbnailData;
+
+/// Field the thumbnail bytes are reported under by [`MediaProperties::collect`].
+const THUMBNAIL_FIELD: &str = "media_properties.thumbnail.data";
+
+/// Everything a [`MockBackend`] holds, in the shape of
+/// [`crate::snapshot::SessionList`].
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MockState {
+    pub sessions: Vec<MockSessionState>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MockSessionState {
+    /// Whether this is the current session; only the first one marked counts.
+    #[serde(default)]
+    pub is_current: bool,
+    /// What the session reports. Its `errors` are reported again whenever
+    /// the section they belong to is read.
+    #[serde(flatten)]
+    pub snapshot: SessionSnapshot,
+    /// Image file holding the thumbnail bytes, relative to the state file.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub thumbnail_file: Option<PathBuf>,
+    #[serde(skip)]
+    pub thumbnail: Option<ThumbnailData>,
+}
+
+impl MockSessionState {
+    /// A session with nothing but an id.
+    pub fn new(id: &str) -> Self {
+        Self {
+            snapshot: SessionSnapshot {
+                app_user_model_id: id.to_string(),
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    pub fn id(&self) -> &str {
+        &self.snapshot.app_user_model_id
+    }
+}
+
+impl MockState {
+    /// Reads a state file: JSON, YAML or TOML by extension, JSON otherwise.
+    /// Besides a session list, a single session snapshot is accepted.
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = fs::read_to_string(path)
+            .with_context(|| format!("could not read {}", path.display()))?;
+        let mut state = match parse::<Self>(path, &text) {
+            Ok(state) => state,
+            Err(list_error) => match parse::<MockSessionState>(path, &text) {
+                Ok(session) => Self {
+                    sessions: vec![MockSessionState {
+                        is_current: true,
+                        ..session
+                    }],
+                },
+                Err(session_error) => bail!(
+                    "{} is neither a session list ({list_error}) nor a single session ({session_error})",
+                    path.display()
+                ),
+            },
+        };
+
+        let dir = path.parent().unwrap_or(Path::new(""));
+        for session in &mut state.sessions {
+            let Some(file) = &session.thumbnail_file else {
+                continue;
+            };
+            let file = dir.join(file);
+            let bytes =
+                fs::read(&file).with_context(|| format!("could not read {}", file.display()))?;
+            let content_type = session
+                .snapshot
+                .media_properties
+                .as_ref()
+                .and_then(|media| media.thumbnail.as_ref())
+                .and_then(|thumbnail| thumbnail.content_type.clone());
+            session.thumbnail = Some(ThumbnailData {
+                content_type,
+                bytes,
+            });
+        }
+        Ok(state)
+    }
+}
+
+fn parse<T: for<'de> Deserialize<'de>>(path: &Path, text: &str) -> Result<T> {
+    let extension = path.extension().and_then(|extension| extension.to_str());
+    Ok(match extension.map(str::to_ascii_lowercase).as_deref() {
+        Some("yaml" | "yml") => serde_yaml::from_str(text)?,
+        Some("toml") => toml::from_str(text)?,
+        _ => serde_json::from_str(text)?,
+    })
+}
+
+/// The state shared by a backend and the sessions it handed out.
+#[derive(Debug, Default)]
+struct Shared {
+    state: MockState,
+    subscribers: Vec<UnboundedSender<MediaEvent>>,
+    /// Commands each session received, by session id.
+    calls: Vec<(String, String)>,
+}
+
+impl Shared {
+    fn session(&self, id: &str) -> Option<&MockSessionState> {
+        self.state
+            .sessions
+            .iter()
+            .find(|session| session.id() == id)
+    }
+
+    fn current(&self) -> Option<&str> {
+        self.state
+            .sessions
+            .iter()
+            .find(|session| session.is_current)
+            .map(MockSessionState::id)
+    }
+
+    fn emit(&mut self, event: MediaEvent) {
+        self.subscribers
+            .retain(|subscriber| subscriber.send(event.clone()).is_ok());
+    }
+
+    /// Applies `change` to the snapshot of `id` and reports the sections it
+    /// changed.
+    fn mutate(&mut self, id: &str, change: impl FnOnce(&mut MockSessionState)) -> Result<()> {
+        let session = self
+            .state
+            .sessions
+            .iter_mut()
+            .find(|session| session.id() == id)
+            .ok_or_else(|| anyhow!("no mock session {id:?}"))?;
+        let before = Sections::of(session);
+        change(session);
+        let after = Sections::of(session);
+
+        let session = id.to_string();
+        if before.media_properties != after.media_properties {
+            self.emit(MediaEvent::MediaPropertiesChanged {
+                session: session.clone(),
+            });
+        }
+        if before.playback_info != after.playback_info {
+            self.emit(MediaEvent::PlaybackInfoChanged {
+                session: session.clone(),
+            });
+        }
+        if before.timeline_properties != after.timeline_properties {
+            self.emit(MediaEvent::TimelinePropertiesChanged { session });
+        }
+        Ok(())
+    }
+}
+
+/// The sections of a session as JSON, to tell which ones a change touched.
+struct Sections {
+    media_properties: serde_json::Value,
+    playback_info: serde_json::Value,
+    timeline_properties: serde_json::Value,
+}
+
+impl Sections {
+    fn of(session: &MockSessionState) -> Self {
+        let snapshot = &session.snapshot;
+        let thumbnail = session.thumbnail.as_ref().map(|data| &data.bytes);
+        Self {
+            media_properties: serde_json::json!([snapshot.media_properties, thumbnail]),
+            playback_info: serde_json::json!(snapshot.playback_info),
+            timeline_properties: serde_json::json!(snapshot.timeline_properties),
+        }
+    }
+}
+
+/// A backend serving sessions from memory.
+#[derive(Debug, Clone, Default)]
+pub struct MockBackend {
+    shared: Arc<Mutex<Shared>>,
+}
+
+impl MockBackend {
+    pub fn new(state: MockState) -> Self {
+        Self {
+            shared: Arc::new(Mutex::new(Shared {
+                state,
+                ..Default::default()
+            })),
+        }
+    }
+
+    /// Serves the sessions of a state file, see [`MockState::load`].
+    pub fn load(path: &Path) -> Result<Self> {
+        Ok(Self::new(MockState::load(path)?))
+    }
+
+    fn shared(&self) -> MutexGuard<'_, Shared> {
+        lock(&self.shared)
+    }
+
+    /// A copy of the current state.
+    pub fn state(&self) -> MockState {
+        self.shared().state.clone()
+    }
+
+    /// Adds `session`, or replaces the one with the same id.
+    pub fn add_session(&self, session: MockSessionState) {
+        let mut shared = self.shared();
+        let id = session.id().to_string();
+        if shared.session(&id).is_some() {
+            let _ = shared.mutate(&id, |existing| *existing = session);
+            return;
+        }
+        let was_current = shared.current().map(str::to_string);
+        shared.state.sessions.push(session);
+        shared.emit(MediaEvent::SessionAdded {
+            session: id.clone(),
+        });
+        if shared.current() != was_current.as_deref() {
+            let session = shared.current().map(str::to_string);
+            shared.emit(MediaEvent::CurrentSessionChanged { session });
+        }
+    }
+
+    pub fn remove_session(&self, id: &str) -> Result<()> {
+        let mut shared = self.shared();
+        let index = shared
+            .state
+            .sessions
+            .iter()
+            .position(|session| session.id() == id)
+            .ok_or_else(|| anyhow!("no mock session {id:?}"))?;
+        let removed = shared.state.sessions.remove(index);
+        shared.emit(MediaEvent::SessionRemoved {
+            session: id.to_string(),
+        });
+        if removed.is_current {
+            let session = shared.current().map(str::to_string);
+            shared.emit(MediaEvent::CurrentSessionChanged { session });
+        }
+        Ok(())
+    }
+
+    /// Makes `id` the current session, or leaves none current.
+    pub fn set_current(&self, id: Option<&str>) -> Result<()> {
+        let mut shared = self.shared();
+        if let Some(id) = id.filter(|id| shared.session(id).is_none()) {
+            bail!("no mock session {id:?}");
+        }
+        if shared.current() == id {
+            return Ok(());
+        }
+        for session in &mut shared.state.sessions {
+            session.is_current = Some(session.id()) == id;
+        }
+        shared.emit(MediaEvent::CurrentSessionChanged {
+            session: id.map(str::to_string),
+        });
+        Ok(())
+    }
+
+    /// Edits the snapshot of `id`, reporting every section that changed.
+    pub fn update(&self, id: &str, change: impl FnOnce(&mut SessionSnapshot)) -> Result<()> {
+        self.shared()
+            .mutate(id, |session| change(&mut session.snapshot))
+    }
+
+    pub fn set_thumbnail(&self, id: &str, thumbnail: Option<ThumbnailData>) -> Result<()> {
+        self.shared()
+            .mutate(id, |session| session.thumbnail = thumbnail)
+    }
+
+    /// Reports that `id` jumped to `position`, as MPRIS players announce
+    /// seeks, after moving its timeline there.
+    pub fn seeked(&self, id: &str, position: Duration) -> Result<()> {
+        let mut shared = self.shared();
+        shared.mutate(id, |session| move_to(&mut session.snapshot, position))?;
+        shared.emit(MediaEvent::Seeked {
+            session: id.to_string(),
+            position,
+        });
+        Ok(())
+    }
+
+    /// Edits the whole state with `change` and reports exactly `events`, for
+    /// changes whose events are already known, as when replaying a trace.
+    pub fn script(
+        &self,
+        change: impl FnOnce(&mut MockState),
+        events: impl IntoIterator<Item = MediaEvent>,
+    ) {
+        let mut shared = self.shared();
+        change(&mut shared.state);
+        for event in events {
+            shared.emit(event);
+        }
+    }
+
+    /// Ends every watch subscription, as if the platform went away.
+    pub fn close(&self) {
+        self.shared().subscribers.clear();
+    }
+
+    /// The commands and settings `id` received so far, such as `play` or
+    /// `rate 1.5`.
+    pub fn calls(&self, id: &str) -> Vec<String> {
+        self.shared()
+            .calls
+            .iter()
+            .filter(|(session, _)| session == id)
+            .map(|(_, call)| call.clone())
+            .collect()
+    }
+
+    fn handle(&self, id: &str) -> Box<dyn MediaSession> {
+        Box::new(MockSession {
+            id: id.to_string(),
+            shared: self.shared.clone(),
+        })
+    }
+}
+
+fn lock(shared: &Mutex<Shared>) -> MutexGuard<'_, Shared> {
+    shared.lock().expect("mock state lock poisoned")
+}
+
+#[async_trait]
+impl MediaBackend for MockBackend {
+    async fn sessions(&self) -> Result<Vec<Box<dyn MediaSession>>> {
+        let ids = self
+            .shared()
+            .state
+            .sessions
+            .iter()
+            .map(|session| session.id().to_string())
+            .collect::<Vec<_>>();
+        Ok(ids.iter().map(|id| self.handle(id)).collect())
+    }
+
+    async fn current_session(&self) -> Result<Option<Box<dyn MediaSession>>> {
+        let current = self.shared().current().map(str::to_string);
+        Ok(current.map(|id| self.handle(&id)))
+    }
+
+    async fn watch(&self) -> Result<UnboundedReceiver<MediaEvent>> {
+        let (tx, rx) = mpsc::unbounded_channel();
+        self.shared().subscribers.push(tx);
+        Ok(rx)
+    }
+}
+
+/// A session of a [`MockBackend`]; reads fail once it was removed.
+struct MockSession {
+    id: String,
+    shared: Arc<Mutex<Shared>>,
+}
+
+impl MockSession {
+    /// Reads one section, replaying the errors recorded for it. The
+    /// thumbnail bytes error is left to [`MediaSession::thumbnail`], which
+    /// is where [`MediaProperties::collect`] looks for it.
+    fn read<T>(
+        &self,
+        errors: &mut FieldErrors,
+        section: impl FnOnce(&SessionSnapshot) -> Option<T>,
+    ) -> Option<T> {
+        let shared = lock(&self.shared);
+        let Some(session) = shared.session(&self.id) else {
+            let gone = anyhow!("mock session {:?} was removed", self.id);
+            return errors.section(Err(gone));
+        };
+        let recorded = session
+            .snapshot
+            .errors
+            .iter()
+            .filter(|error| error.field != THUMBNAIL_FIELD)
+            .cloned()
+            .collect::<Vec<_>>();
+        errors.replay(&recorded);
+        section(&session.snapshot)
+    }
+
+    /// Applies a command or setting, recording it as `call`.
+    fn apply(&self, call: String, change: impl FnOnce(&mut SessionSnapshot)) -> Result<bool> {
+        let mut shared = lock(&self.shared);
+        shared.mutate(&self.id, |session| change(&mut session.snapshot))?;
+        shared.calls.push((self.id.clone(), call));
+        Ok(true)
+    }
+}
+
+#[async_trait]
+impl MediaSession for MockSession {
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    async fn media_properties(&self, errors: &mut FieldErrors) -> Option<MediaProperties> {
+        self.read(errors, |snapshot| snapshot.media_properties.clone())
+    }
+
+    async fn playback_info(&self, errors: &mut FieldErrors) -> Option<PlaybackInfo> {
+        self.read(errors, |snapshot| snapshot.playback_info.clone())
+    }
+
+    async fn timeline_properties(&self, errors: &mut FieldErrors) -> Option<TimelineProperties> {
+        self.read(errors, |snapshot| snapshot.timeline_properties.clone())
+    }
+
+    /// Fails with the recorded `thumbnail.data` error, if there is one.
+    async fn thumbnail(&self) -> Result<Option<ThumbnailData>> {
+        let shared = lock(&self.shared);
+        let session = shared
+            .session(&self.id)
+            .ok_or_else(|| anyhow!("mock session {:?} was removed", self.id))?;
+        let recorded = session
+            .snapshot
+            .errors
+            .iter()
+            .find(|error| error.field == THUMBNAIL_FIELD);
+        match recorded {
+            Some(error) => Err(error.clone().into()),
+            None => Ok(session.thumbnail.clone()),
+        }
+    }
+
+    /// Play, pause, toggle and stop change the playback status; next and
+    /// previous are only recorded.
+    async fn send_command(&self, command: PlaybackCommand) -> Result<bool> {
+        self.apply(command.to_string(), |snapshot| {
+            let status = snapshot
+                .playback_info
+                .as_ref()
+                .and_then(|playback_info| playback_info.playback_status);
+            let status = match command {
+                PlaybackCommand::Play => PlaybackStatus::Playing,
+                PlaybackCommand::Pause => PlaybackStatus::Paused,
+                PlaybackCommand::TogglePlayPause if status == Some(PlaybackStatus::Playing) => {
+                    PlaybackStatus::Paused
+                }
+                PlaybackCommand::TogglePlayPause => PlaybackStatus::Playing,
+                PlaybackCommand::Stop => PlaybackStatus::Stopped,
+                PlaybackCommand::Next | PlaybackCommand::Previous => return,
+            };
+            // Keep the position reached so far before the status changes.
+            if let Some(position) = estimate(snapshot) {
+                move_to(snapshot, position);
+            }
+            playback_info(snapshot).playback_status = Some(status);
+        })
+    }
+
+    async fn set_position(&self, position: Duration) -> Result<bool> {
+        let call = format!("position {}", duration::format(position));
+        self.apply(call, |snapshot| move_to(snapshot, position))
+    }
+
+    async fn set_repeat_mode(&self, mode: RepeatMode) -> Result<bool> {
+        self.apply(format!("repeat {mode}"), |snapshot| {
+            playback_info(snapshot).auto_repeat_mode = Some(mode);
+        })
+    }
+
+    async fn set_shuffle(&self, active: bool) -> Result<bool> {
+        self.apply(format!("shuffle {active}"), |snapshot| {
+            playback_info(snapshot).is_shuffle_active = Some(active);
+        })
+    }
+
+    async fn set_playback_rate(&self, rate: f64) -> Result<bool> {
+        self.apply(format!("rate {rate}"), |snapshot| {
+            if let Some(position) = estimate(snapshot) {
+                move_to(snapshot, position);
+            }
+            playback_info(snapshot).playback_rate = Some(rate);
+        })
+    }
+}
+
+fn playback_info(snapshot: &mut SessionSnapshot) -> &mut PlaybackInfo {
+    snapshot.playback_info.get_or_insert_with(Default::default)
+}
+
+fn estimate(snapshot: &SessionSnapshot) -> Option<Duration> {
+    let timeline = snapshot.timeline_properties.as_ref()?;
+    let estimator = PositionEstimator::new(timeline, snapshot.playback_info.as_ref())?;
+    Some(estimator.now())
+}
+
+/// Reports `position` as of now.
+fn move_to(snapshot: &mut SessionSnapshot, position: Duration) {
+    let timeline = snapshot
+        .timeline_properties
+        .get_or_insert_with(Default::default);
+    timeline.position = Some(position);
+    timeline.last_updated_time = Some(universal_time(SystemTime::now()));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::control::{self, SeekOffset};
+    use crate::format;
+    use crate::snapshot::{FieldError, PlaybackControls, SessionList};
+
+    const LIST: &str = r#"{
+        "sessions": [
+            {
+                "is_current": false,
+                "app_user_model_id": "first",
+                "media_properties": null,
+                "playback_info": null,
+                "timeline_properties": null
+            },
+            {
+                "is_current": true,
+                "app_user_model_id": "second",
+                "media_properties": { "title": "Song", "genres": [] },
+                "playback_info": {
+                    "playback_status": "Paused",
+                    "controls": { "is_play_enabled": true, "is_next_enabled": false }
+                },
+                "timeline_properties": {
+                    "end_time": 180000000000,
+                    "position": 42000000000
+                },
+                "errors": [
+                    { "field": "media_properties.artist", "code": "0x80004005", "message": "Unspecified error" }
+                ]
+            }
+        ]
+    }"#;
+
+    fn backend() -> MockBackend {
+        MockBackend::new(serde_json::from_str(LIST).unwrap())
+    }
+
+    fn drain(events: &mut UnboundedReceiver<MediaEvent>) -> Vec<MediaEvent> {
+        let mut drained = vec![];
+        while let Ok(event) = events.try_recv() {
+            drained.push(event);
+        }
+        drained
+    }
+
+    #[tokio::test]
+    async fn reads_a_session_list() {
+        let backend = backend();
+        let list = SessionList::collect(&backend).await.unwrap();
+        let ids = list
+            .sessions
+            .iter()
+            .map(|session| {
+                (
+                    session.snapshot.app_user_model_id.as_str(),
+                    session.is_current,
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(ids, [("first", false), ("second", true)]);
+
+        let current = list.sessions[1].snapshot.clone();
+        let title = current.media_properties.and_then(|media| media.title);
+        assert_eq!(title.as_deref(), Some("Song"));
+    }
+
+    #[tokio::test]
+    async fn replays_recorded_errors() {
+        let backend = backend();
+        let session = backend.current_session().await.unwrap().unwrap();
+        let snapshot = SessionSnapshot::collect(session.as_ref()).await;
+        assert_eq!(
+            snapshot.errors,
+            [FieldError {
+                field: "media_properties.artist".to_string(),
+                code: Some("0x80004005".to_string()),
+                message: "Unspecified error".to_string(),
+            }]
+        );
+    }
+
+    #[tokio::test]
+    async fn reports_changed_sections() {
+        let backend = backend();
+        let mut events = backend.watch().await.unwrap();
+        backend
+            .update("second", |snapshot| {
+                snapshot.media_properties.as_mut().unwrap().title = Some("Other".to_string());
+            })
+            .unwrap();
+        backend.update("second", |_| {}).unwrap();
+        assert_eq!(
+            drain(&mut events),
+            [MediaEvent::MediaPropertiesChanged {
+                session: "second".to_string()
+            }]
+        );
+    }
+
+    #[tokio::test]
+    async fn reports_session_changes() {
+        let backend = backend();
+        let mut events = backend.watch().await.unwrap();
+        backend.add_session(MockSessionState::new("third"));
+        backend.set_current(Some("third")).unwrap();
+        backend.remove_session("third").unwrap();
+        assert_eq!(
+            drain(&mut events),
+            [
+                MediaEvent::SessionAdded {
+                    session: "third".to_string()
+                },
+                MediaEvent::CurrentSessionChanged {
+                    session: Some("third".to_string())
+                },
+                MediaEvent::SessionRemoved {
+                    session: "third".to_string()
+                },
+                MediaEvent::CurrentSessionChanged { session: None },
+            ]
+        );
+        assert!(backend.current_session().await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn commands_change_the_session() {
+        let backend = backend();
+        let mut events = backend.watch().await.unwrap();
+        let session = backend.session("second").await.unwrap().unwrap();
+
+        let outcome = control::send(session.as_ref(), PlaybackCommand::Play)
+            .await
+            .unwrap();
+        assert!(outcome.succeeded());
+        let status = backend.state().sessions[1]
+            .snapshot
+            .playback_info
+            .as_ref()
+            .and_then(|playback_info| playback_info.playback_status);
+        assert_eq!(status, Some(PlaybackStatus::Playing));
+        assert!(
+            drain(&mut events).contains(&MediaEvent::PlaybackInfoChanged {
+                session: "second".to_string()
+            })
+        );
+
+        let error = control::send(session.as_ref(), PlaybackCommand::Next)
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("is_next_enabled is false"));
+        assert_eq!(backend.calls("second"), ["play"]);
+    }
+
+    #[tokio::test]
+    async fn setters_are_read_back() {
+        let backend = backend();
+        backend
+            .update("second", |snapshot| {
+                let playback_info = snapshot.playback_info.as_mut().unwrap();
+                playback_info.controls = Some(PlaybackControls::default());
+            })
+            .unwrap();
+        let session = backend.session("second").await.unwrap().unwrap();
+
+        let outcome = control::set_playback_rate(session.as_ref(), 1.5)
+            .await
+            .unwrap();
+        assert_eq!(outcome.applied, Some(true));
+        let outcome = control::seek(
+            session.as_ref(),
+            SeekOffset {
+                backward: true,
+                amount: Duration::from_secs(2),
+            },
+        )
+        .await
+        .unwrap();
+        assert_eq!(outcome.position, Some(Duration::from_secs(40)));
+        assert_eq!(backend.calls("second"), ["rate 1.5", "position 00:40.000"]);
+    }
+
+    #[tokio::test]
+    async fn seeks_from_the_extrapolated_position() {
+        let backend = backend();
+        backend
+            .update("second", |snapshot| {
+                let playback_info = snapshot.playback_info.as_mut().unwrap();
+                playback_info.playback_status = Some(PlaybackStatus::Playing);
+                playback_info.controls = Some(PlaybackControls::default());
+                let timeline = snapshot.timeline_properties.as_mut().unwrap();
+                timeline.position = Some(Duration::from_secs(10));
+                let anchor = SystemTime::now() - Duration::from_secs(30);
+                timeline.last_updated_time = Some(universal_time(anchor));
+            })
+            .unwrap();
+        let session = backend.session("second").await.unwrap().unwrap();
+
+        let outcome = control::seek(
+            session.as_ref(),
+            SeekOffset {
+                backward: false,
+                amount: Duration::from_secs(5),
+            },
+        )
+        .await
+        .unwrap();
+        let position = outcome.position.unwrap();
+        assert!(
+            (Duration::from_secs(45)..Duration::from_secs(46)).contains(&position),
+            "sought to {position:?}"
+        );
+    }
+
+    #[test]
+    fn loads_a_single_snapshot() {
+        let dir = std::env::temp_dir().join(format!("test-gsmtc-mock-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("session.yaml");
+        fs::write(
+            &path,
+            "app_user_model_id: solo\nmedia_properties: null\nplayback_info: null\ntimeline_properties: null\n",
+        )
+        .unwrap();
+
+        let state = MockState::load(&path).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(state.sessions.len(), 1);
+        assert_eq!(state.sessions[0].id(), "solo");
+        assert!(state.sessions[0].is_current);
+    }
+
+    #[tokio::test]
+    async fn loads_listings_it_rendered() {
+        let list = SessionList::collect(&backend()).await.unwrap();
+        let dir = std::env::temp_dir().join(format!("test-gsmtc-render-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let rendered = [
+            (
+                "sessions.yaml",
+                format::write_yaml as fn(&mut Vec<u8>, &SessionList) -> _,
+            ),
+            ("sessions.toml", format::write_toml),
+        ];
+        for (name, write) in rendered {
+            let mut output = vec![];
+            write(&mut output, &list).unwrap();
+            let path = dir.join(name);
+            fs::write(&path, output).unwrap();
+
+            let state = MockState::load(&path).unwrap();
+            assert_eq!(state.sessions.len(), list.sessions.len(), "{name}");
+            for (loaded, listed) in state.sessions.iter().zip(&list.sessions) {
+                assert_eq!(loaded.is_current, listed.is_current, "{name}");
+                assert_eq!(
+                    serde_json::to_value(&loaded.snapshot).unwrap(),
+                    serde_json::to_value(&listed.snapshot).unwrap(),
+                    "{name}"
+                );
+            }
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
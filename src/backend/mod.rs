@@ -0,0 +1,204 @@
+//! Platform-neutral access to system media sessions.
+//!
+//! Each platform integration implements [`MediaBackend`] to enumerate sessions
+//! and [`MediaSession`] to read them; everything above this module only deals
+//! with the owned values from [`crate::snapshot`].
+
+use std::fmt;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::Result;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc::UnboundedReceiver;
+
+use crate::control::{PlaybackCommand, SeekOffset};
+use crate::snapshot::{
+    nanos, FieldErrors, MediaProperties, PlaybackInfo, RepeatMode, TimelineProperties,
+};
+use crate::thumbnail::ThumbnailData;
+
+#[cfg(windows)]
+pub mod gsmtc;
+pub mod mock;
+#[cfg(target_os = "linux")]
+pub mod mpris;
+pub mod remote;
+pub mod replay;
+
+/// Well-known bus name prefix shared by every MPRIS2 player.
+pub const MPRIS_BUS_NAME_PREFIX: &str = "org.mpris.MediaPlayer2.";
+
+#[async_trait]
+pub trait MediaBackend: Send + Sync {
+    /// All sessions currently known to the platform.
+    async fn sessions(&self) -> Result<Vec<Box<dyn MediaSession>>>;
+
+    /// The session the platform considers current, if any.
+    async fn current_session(&self) -> Result<Option<Box<dyn MediaSession>>>;
+
+    /// The session whose [`MediaSession::id`] is exactly `id`.
+    async fn session(&self, id: &str) -> Result<Option<Box<dyn MediaSession>>> {
+        Ok(self
+            .sessions()
+            .await?
+            .into_iter()
+            .find(|session| session.id() == id))
+    }
+
+    /// Subscribes to changes of the session list and of every session in it.
+    /// The subscription ends once the receiver is dropped.
+    async fn watch(&self) -> Result<UnboundedReceiver<MediaEvent>>;
+}
+
+#[async_trait]
+pub trait MediaSession: Send + Sync {
+    /// Identifier of the application owning the session
+    /// (`SourceAppUserModelId` on Windows).
+    fn id(&self) -> &str;
+
+    // Each section reader records every value it fails to read in `errors`
+    // and returns `None` only when the section as a whole is unavailable.
+
+    async fn media_properties(&self, errors: &mut FieldErrors) -> Option<MediaProperties>;
+
+    async fn playback_info(&self, errors: &mut FieldErrors) -> Option<PlaybackInfo>;
+
+    async fn timeline_properties(&self, errors: &mut FieldErrors) -> Option<TimelineProperties>;
+
+    /// The cover art itself; `None` when the session has none.
+    async fn thumbnail(&self) -> Result<Option<ThumbnailData>>;
+
+    /// Asks the player to carry out `command`, returning whether it accepted
+    /// the request. Callers check the controls first; see [`crate::control::send`].
+    async fn send_command(&self, command: PlaybackCommand) -> Result<bool>;
+
+    /// Asks the player to move to `position` from the start of the track.
+    async fn set_position(&self, position: Duration) -> Result<bool>;
+
+    /// Asks the player to move by `offset` from `current`, the position last
+    /// read from it. Players that seek relative to their own position
+    /// override this and ignore `current`.
+    async fn seek(&self, current: Duration, offset: SeekOffset) -> Result<bool> {
+        match offset.apply(current) {
+            Some(position) => self.set_position(position).await,
+            None => anyhow::bail!("cannot seek to before the start"),
+        }
+    }
+
+    // The setters below return whether the player accepted the request; the
+    // new value may only show up in `playback_info` some time later.
+
+    async fn set_repeat_mode(&self, mode: RepeatMode) -> Result<bool>;
+
+    async fn set_shuffle(&self, active: bool) -> Result<bool>;
+
+    async fn set_playback_rate(&self, rate: f64) -> Result<bool>;
+}
+
+/// A change reported by a backend. Session fields hold [`MediaSession::id`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum MediaEvent {
+    SessionAdded {
+        session: String,
+    },
+    SessionRemoved {
+        session: String,
+    },
+    CurrentSessionChanged {
+        session: Option<String>,
+    },
+    MediaPropertiesChanged {
+        session: String,
+    },
+    PlaybackInfoChanged {
+        session: String,
+    },
+    TimelinePropertiesChanged {
+        session: String,
+    },
+    /// The player jumped to `position` (MPRIS `Seeked`).
+    Seeked {
+        session: String,
+        #[serde(with = "nanos")]
+        position: Duration,
+    },
+}
+
+impl MediaEvent {
+    pub fn session(&self) -> Option<&str> {
+        match self {
+            Self::SessionAdded { session }
+            | Self::SessionRemoved { session }
+            | Self::MediaPropertiesChanged { session }
+            | Self::PlaybackInfoChanged { session }
+            | Self::TimelinePropertiesChanged { session }
+            | Self::Seeked { session, .. } => Some(session),
+            Self::CurrentSessionChanged { session } => session.as_deref(),
+        }
+    }
+
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::SessionAdded { .. } => "session_added",
+            Self::SessionRemoved { .. } => "session_removed",
+            Self::CurrentSessionChanged { .. } => "current_session_changed",
+            Self::MediaPropertiesChanged { .. } => "media_properties_changed",
+            Self::PlaybackInfoChanged { .. } => "playback_info_changed",
+            Self::TimelinePropertiesChanged { .. } => "timeline_properties_changed",
+            Self::Seeked { .. } => "seeked",
+        }
+    }
+}
+
+impl fmt::Display for MediaEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.kind())?;
+        if let Some(session) = self.session() {
+            write!(f, " \"{session}\"")?;
+        }
+        if let Self::Seeked { position, .. } = self {
+            write!(f, " {}", position.as_nanos())?;
+        }
+        Ok(())
+    }
+}
+
+/// Connects to the media backend of the running platform.
+pub async fn default_backend() -> Result<Box<dyn MediaBackend>> {
+    #[cfg(windows)]
+    {
+        Ok(Box::new(gsmtc::GsmtcBackend::new().await?))
+    }
+    #[cfg(target_os = "linux")]
+    {
+        Ok(Box::new(mpris::MprisBackend::new().await?))
+    }
+    #[cfg(not(any(windows, target_os = "linux")))]
+    {
+        anyhow::bail!("no media backend is available on this platform")
+    }
+}
+
+/// Offset between the Windows epoch (1601-01-01) and the Unix epoch, in 100ns ticks.
+const UNIX_EPOCH_TICKS: i64 = 116_444_736_000_000_000;
+
+/// Converts a system time to 100ns ticks since 1601-01-01 UTC.
+pub fn universal_time(time: SystemTime) -> i64 {
+    match time.duration_since(UNIX_EPOCH) {
+        Ok(since) => UNIX_EPOCH_TICKS + (since.as_nanos() / 100) as i64,
+        Err(before) => UNIX_EPOCH_TICKS - (before.duration().as_nanos() / 100) as i64,
+    }
+}
+
+/// Converts 100ns ticks since 1601-01-01 UTC to a system time.
+pub fn system_time(ticks: i64) -> SystemTime {
+    let since_unix = ticks.saturating_sub(UNIX_EPOCH_TICKS);
+    let offset = Duration::from_nanos(since_unix.unsigned_abs().saturating_mul(100));
+    if since_unix >= 0 {
+        UNIX_EPOCH + offset
+    } else {
+        UNIX_EPOCH - offset
+    }
+}
@@ -0,0 +1,1024 @@
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::fs;
+use std::os::unix::ffi::OsStringExt;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use anyhow::{anyhow, bail, Context, Result};
+use async_trait::async_trait;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use futures_util::StreamExt;
+use percent_encoding::percent_decode_str;
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+use tokio::task::JoinHandle;
+use zbus::fdo::{DBusProxy, PropertiesProxy};
+use zbus::proxy::CacheProperties;
+use zbus::zvariant::{ObjectPath, OwnedValue, Value};
+use zbus::{proxy, Connection, DBusError};
+
+use super::{universal_time, MediaBackend, MediaEvent, MediaSession, MPRIS_BUS_NAME_PREFIX};
+use crate::control::{PlaybackCommand, SeekOffset};
+use crate::snapshot::{
+    FieldErrors, MediaProperties, PlatformError, PlaybackControls, PlaybackInfo, PlaybackStatus,
+    RepeatMode, Thumbnail, TimelineProperties,
+};
+use crate::thumbnail::ThumbnailData;
+
+const OBJECT_PATH: &str = "/org/mpris/MediaPlayer2";
+const PLAYER_INTERFACE: &str = "org.mpris.MediaPlayer2.Player";
+
+#[proxy(
+    interface = "org.mpris.MediaPlayer2.Player",
+    default_path = "/org/mpris/MediaPlayer2"
+)]
+trait Player {
+    fn play(&self) -> zbus::Result<()>;
+
+    fn pause(&self) -> zbus::Result<()>;
+
+    fn play_pause(&self) -> zbus::Result<()>;
+
+    fn next(&self) -> zbus::Result<()>;
+
+    fn previous(&self) -> zbus::Result<()>;
+
+    fn stop(&self) -> zbus::Result<()>;
+
+    fn seek(&self, offset: i64) -> zbus::Result<()>;
+
+    fn set_position(&self, track_id: &ObjectPath<'_>, position: i64) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    fn seeked(&self, position: i64) -> zbus::Result<()>;
+
+    #[zbus(property)]
+    fn playback_status(&self) -> zbus::Result<String>;
+
+    #[zbus(property)]
+    fn loop_status(&self) -> zbus::Result<String>;
+
+    #[zbus(property)]
+    fn set_loop_status(&self, value: &str) -> zbus::Result<()>;
+
+    #[zbus(property)]
+    fn rate(&self) -> zbus::Result<f64>;
+
+    #[zbus(property)]
+    fn set_rate(&self, value: f64) -> zbus::Result<()>;
+
+    #[zbus(property)]
+    fn minimum_rate(&self) -> zbus::Result<f64>;
+
+    #[zbus(property)]
+    fn maximum_rate(&self) -> zbus::Result<f64>;
+
+    #[zbus(property)]
+    fn shuffle(&self) -> zbus::Result<bool>;
+
+    #[zbus(property)]
+    fn set_shuffle(&self, value: bool) -> zbus::Result<()>;
+
+    #[zbus(property)]
+    fn metadata(&self) -> zbus::Result<HashMap<String, OwnedValue>>;
+
+    #[zbus(property)]
+    fn position(&self) -> zbus::Result<i64>;
+
+    #[zbus(property)]
+    fn can_go_next(&self) -> zbus::Result<bool>;
+
+    #[zbus(property)]
+    fn can_go_previous(&self) -> zbus::Result<bool>;
+
+    #[zbus(property)]
+    fn can_play(&self) -> zbus::Result<bool>;
+
+    #[zbus(property)]
+    fn can_pause(&self) -> zbus::Result<bool>;
+
+    #[zbus(property)]
+    fn can_seek(&self) -> zbus::Result<bool>;
+
+    #[zbus(property)]
+    fn can_control(&self) -> zbus::Result<bool>;
+}
+
+pub struct MprisBackend {
+    connection: Connection,
+}
+
+impl MprisBackend {
+    /// Connects to the session bus named by `DBUS_SESSION_BUS_ADDRESS`.
+    pub async fn new() -> Result<Self> {
+        Ok(Self::with_connection(Connection::session().await?))
+    }
+
+    pub fn with_connection(connection: Connection) -> Self {
+        Self { connection }
+    }
+
+    async fn player_names(&self) -> Result<Vec<String>> {
+        let dbus = DBusProxy::new(&self.connection).await?;
+        let mut names = dbus
+            .list_names()
+            .await?
+            .into_iter()
+            .map(|name| name.to_string())
+            .filter(|name| name.starts_with(MPRIS_BUS_NAME_PREFIX))
+            .collect::<Vec<_>>();
+        names.sort();
+        Ok(names)
+    }
+
+    async fn players(&self) -> Result<Vec<MprisSession>> {
+        let mut players = vec![];
+        for name in self.player_names().await? {
+            players.push(MprisSession::new(&self.connection, name).await?);
+        }
+        Ok(players)
+    }
+
+    async fn current_player_name(&self) -> Option<String> {
+        let current = self.current_session().await.ok()??;
+        Some(current.id().to_string())
+    }
+}
+
+#[async_trait]
+impl MediaBackend for MprisBackend {
+    async fn sessions(&self) -> Result<Vec<Box<dyn MediaSession>>> {
+        Ok(self
+            .players()
+            .await?
+            .into_iter()
+            .map(|player| Box::new(player) as Box<dyn MediaSession>)
+            .collect())
+    }
+
+    /// MPRIS has no notion of a current player, so prefer the first one that
+    /// is playing, then the first paused one, then whichever comes first.
+    async fn current_session(&self) -> Result<Option<Box<dyn MediaSession>>> {
+        let mut players = vec![];
+        for player in self.players().await? {
+            let status = player.proxy.playback_status().await.unwrap_or_default();
+            players.push((status, player));
+        }
+
+        let index = ["Playing", "Paused"]
+            .iter()
+            .find_map(|wanted| players.iter().position(|(status, _)| status == wanted))
+            .unwrap_or(0);
+        if index >= players.len() {
+            return Ok(None);
+        }
+        Ok(Some(Box::new(players.swap_remove(index).1)))
+    }
+
+    /// Follows `NameOwnerChanged` for players coming and going, and each
+    /// player's `PropertiesChanged` and `Seeked` signals. MPRIS has no current
+    /// player, so the choice made by [`Self::current_session`] is re-evaluated
+    /// after every event.
+    async fn watch(&self) -> Result<UnboundedReceiver<MediaEvent>> {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let (player_tx, mut player_rx) = mpsc::unbounded_channel();
+
+        let dbus = DBusProxy::new(&self.connection).await?;
+        let mut owner_changes = dbus.receive_name_owner_changed().await?;
+
+        let mut players = HashMap::new();
+        for name in self.player_names().await? {
+            let task = watch_player(&self.connection, &name, player_tx.clone()).await?;
+            players.insert(name, task);
+        }
+
+        let backend = MprisBackend::with_connection(self.connection.clone());
+        let mut current = backend.current_player_name().await;
+        tokio::spawn(async move {
+            loop {
+                let events = tokio::select! {
+                    Some(event) = player_rx.recv() => vec![event],
+                    Some(signal) = owner_changes.next() => {
+                        let Ok(args) = signal.args() else { continue };
+                        let name = args.name().to_string();
+                        if !name.starts_with(MPRIS_BUS_NAME_PREFIX) {
+                            continue;
+                        }
+
+                        // A name changing hands is reported as the old player
+                        // leaving and a new one arriving.
+                        let mut events = vec![];
+                        if let Some(task) = players.remove(&name) {
+                            task.abort();
+                            events.push(MediaEvent::SessionRemoved { session: name.clone() });
+                        }
+                        if args.new_owner().is_some() {
+                            let task = watch_player(&backend.connection, &name, player_tx.clone());
+                            if let Ok(task) = task.await {
+                                players.insert(name.clone(), task);
+                                events.push(MediaEvent::SessionAdded { session: name });
+                            }
+                        }
+                        events
+                    }
+                    else => break,
+                };
+
+                if events.into_iter().any(|event| tx.send(event).is_err()) {
+                    break;
+                }
+
+                let new_current = backend.current_player_name().await;
+                if new_current != current {
+                    current = new_current;
+                    let event = MediaEvent::CurrentSessionChanged {
+                        session: current.clone(),
+                    };
+                    if tx.send(event).is_err() {
+                        break;
+                    }
+                }
+            }
+            for task in players.into_values() {
+                task.abort();
+            }
+        });
+
+        Ok(rx)
+    }
+}
+
+/// Forwards the signals of a single player until the task is aborted.
+async fn watch_player(
+    connection: &Connection,
+    bus_name: &str,
+    tx: UnboundedSender<MediaEvent>,
+) -> Result<JoinHandle<()>> {
+    let properties = PropertiesProxy::builder(connection)
+        .destination(bus_name.to_string())?
+        .path(OBJECT_PATH)?
+        .build()
+        .await?;
+    let player = PlayerProxy::builder(connection)
+        .destination(bus_name.to_string())?
+        .cache_properties(CacheProperties::No)
+        .build()
+        .await?;
+    let mut changes = properties.receive_properties_changed().await?;
+    let mut seeks = player.receive_seeked().await?;
+    let session = bus_name.to_string();
+
+    Ok(tokio::spawn(async move {
+        loop {
+            let events = tokio::select! {
+                Some(signal) = changes.next() => {
+                    let Ok(args) = signal.args() else { continue };
+                    if args.interface_name() != PLAYER_INTERFACE {
+                        continue;
+                    }
+                    let names = args
+                        .changed_properties()
+                        .keys()
+                        .copied()
+                        .chain(args.invalidated_properties().iter().copied());
+                    property_events(&session, names)
+                }
+                Some(signal) = seeks.next() => {
+                    let Ok(args) = signal.args() else { continue };
+                    vec![MediaEvent::Seeked {
+                        session: session.clone(),
+                        position: micros(*args.position()),
+                    }]
+                }
+                else => break,
+            };
+            if events.into_iter().any(|event| tx.send(event).is_err()) {
+                break;
+            }
+        }
+    }))
+}
+
+/// Maps changed `org.mpris.MediaPlayer2.Player` properties to the sections
+/// they belong to, reporting each section once.
+fn property_events<'a>(session: &str, names: impl Iterator<Item = &'a str>) -> Vec<MediaEvent> {
+    let mut events = vec![];
+    for name in names {
+        let changed = match name {
+            "Metadata" => vec![
+                MediaEvent::MediaPropertiesChanged {
+                    session: session.to_string(),
+                },
+                MediaEvent::TimelinePropertiesChanged {
+                    session: session.to_string(),
+                },
+            ],
+            "Position" => vec![MediaEvent::TimelinePropertiesChanged {
+                session: session.to_string(),
+            }],
+            _ => vec![MediaEvent::PlaybackInfoChanged {
+                session: session.to_string(),
+            }],
+        };
+        for event in changed {
+            if !events.contains(&event) {
+                events.push(event);
+            }
+        }
+    }
+    events
+}
+
+pub struct MprisSession {
+    bus_name: String,
+    proxy: PlayerProxy<'static>,
+}
+
+impl MprisSession {
+    async fn new(connection: &Connection, bus_name: String) -> Result<Self> {
+        // Position is never announced through PropertiesChanged, so a cache
+        // would serve stale values.
+        let proxy = PlayerProxy::builder(connection)
+            .destination(bus_name.clone())?
+            .cache_properties(CacheProperties::No)
+            .build()
+            .await?;
+        Ok(Self { bus_name, proxy })
+    }
+}
+
+#[async_trait]
+impl MediaSession for MprisSession {
+    fn id(&self) -> &str {
+        &self.bus_name
+    }
+
+    async fn media_properties(&self, errors: &mut FieldErrors) -> Option<MediaProperties> {
+        // Metadata keys a player leaves out are simply absent, not errors.
+        let metadata = errors.section(self.proxy.metadata().await)?;
+
+        Some(MediaProperties {
+            album_artist: string_list(&metadata, "xesam:albumArtist").map(|v| v.join(", ")),
+            album_title: string(&metadata, "xesam:album"),
+            album_track_count: None,
+            artist: string_list(&metadata, "xesam:artist").map(|v| v.join(", ")),
+            genres: string_list(&metadata, "xesam:genre").unwrap_or_default(),
+            playback_type: None,
+            subtitle: None,
+            thumbnail: string(&metadata, "mpris:artUrl").map(|url| thumbnail(&url)),
+            title: string(&metadata, "xesam:title"),
+            track_number: integer(&metadata, "xesam:trackNumber").map(|v| v as i32),
+        })
+    }
+
+    async fn thumbnail(&self) -> Result<Option<ThumbnailData>> {
+        let metadata = self.proxy.metadata().await?;
+        match string(&metadata, "mpris:artUrl") {
+            Some(url) => fetch_art(&url),
+            None => Ok(None),
+        }
+    }
+
+    /// Properties without a field of their own, such as `CanControl` or
+    /// `MinimumRate`, are reported under their snake_case name. Players
+    /// leaving out the optional `LoopStatus`, `Shuffle` or `Rate` simply do
+    /// not report those fields.
+    async fn playback_info(&self, errors: &mut FieldErrors) -> Option<PlaybackInfo> {
+        let can_control = errors.read("can_control", self.proxy.can_control().await);
+        let can_pause = errors.read("controls.is_pause_enabled", self.proxy.can_pause().await);
+        let loop_status = errors
+            .read("auto_repeat_mode", optional(self.proxy.loop_status().await))
+            .flatten();
+        let shuffle = errors
+            .read("is_shuffle_active", optional(self.proxy.shuffle().await))
+            .flatten();
+        let auto_repeat_mode = loop_status
+            .as_deref()
+            .and_then(|status| errors.read("auto_repeat_mode", RepeatMode::from_mpris(status)));
+        let rate_range = (
+            errors.read("minimum_rate", self.proxy.minimum_rate().await),
+            errors.read("maximum_rate", self.proxy.maximum_rate().await),
+        );
+
+        let controls = PlaybackControls {
+            is_next_enabled: errors
+                .read("controls.is_next_enabled", self.proxy.can_go_next().await),
+            is_pause_enabled: can_pause,
+            is_playback_position_enabled: errors.read(
+                "controls.is_playback_position_enabled",
+                self.proxy.can_seek().await,
+            ),
+            is_playback_rate_enabled: can_control
+                .map(|v| v && rate_range.0.unwrap_or(1.0) < rate_range.1.unwrap_or(1.0)),
+            is_play_enabled: errors.read("controls.is_play_enabled", self.proxy.can_play().await),
+            is_play_pause_toggle_enabled: can_pause,
+            is_previous_enabled: errors.read(
+                "controls.is_previous_enabled",
+                self.proxy.can_go_previous().await,
+            ),
+            is_repeat_enabled: can_control.map(|v| v && loop_status.is_some()),
+            is_shuffle_enabled: can_control.map(|v| v && shuffle.is_some()),
+            is_stop_enabled: can_control,
+            ..Default::default()
+        };
+
+        Some(PlaybackInfo {
+            auto_repeat_mode,
+            controls: Some(controls),
+            is_shuffle_active: shuffle,
+            playback_rate: errors
+                .read("playback_rate", optional(self.proxy.rate().await))
+                .flatten(),
+            playback_status: errors
+                .read("playback_status", self.proxy.playback_status().await)
+                .and_then(|status| {
+                    errors.read("playback_status", PlaybackStatus::from_mpris(&status))
+                }),
+            playback_type: None,
+        })
+    }
+
+    async fn timeline_properties(&self, errors: &mut FieldErrors) -> Option<TimelineProperties> {
+        let metadata = errors.section(self.proxy.metadata().await)?;
+        let length = integer(&metadata, "mpris:length").map(micros);
+        let can_seek = errors.read("max_seek_time", self.proxy.can_seek().await);
+
+        Some(TimelineProperties {
+            start_time: Some(Duration::ZERO),
+            end_time: length,
+            max_seek_time: match can_seek {
+                Some(true) => length,
+                Some(false) => Some(Duration::ZERO),
+                None => None,
+            },
+            min_seek_time: Some(Duration::ZERO),
+            position: errors
+                .read("position", self.proxy.position().await)
+                .map(micros),
+            last_updated_time: Some(universal_time(SystemTime::now())),
+        })
+    }
+
+    /// MPRIS methods return nothing, so a call that did not fail counts as
+    /// accepted.
+    async fn send_command(&self, command: PlaybackCommand) -> Result<bool> {
+        match command {
+            PlaybackCommand::Play => self.proxy.play().await?,
+            PlaybackCommand::Pause => self.proxy.pause().await?,
+            PlaybackCommand::TogglePlayPause => self.proxy.play_pause().await?,
+            PlaybackCommand::Next => self.proxy.next().await?,
+            PlaybackCommand::Previous => self.proxy.previous().await?,
+            PlaybackCommand::Stop => self.proxy.stop().await?,
+        }
+        Ok(true)
+    }
+
+    async fn set_position(&self, position: Duration) -> Result<bool> {
+        let metadata = self.proxy.metadata().await?;
+        let position = position.as_micros() as i64;
+        match string(&metadata, "mpris:trackid") {
+            Some(track_id) => {
+                let track_id = ObjectPath::try_from(track_id.as_str())?;
+                self.proxy.set_position(&track_id, position).await?;
+            }
+            // Players ignore SetPosition without a track id, so jump relative
+            // to where the player is instead.
+            None => {
+                let current = self.proxy.position().await?;
+                self.proxy.seek(position - current).await?;
+            }
+        }
+        Ok(true)
+    }
+
+    async fn seek(&self, _current: Duration, offset: SeekOffset) -> Result<bool> {
+        let amount = offset.amount.as_micros() as i64;
+        let offset = if offset.backward { -amount } else { amount };
+        self.proxy.seek(offset).await?;
+        Ok(true)
+    }
+
+    async fn set_repeat_mode(&self, mode: RepeatMode) -> Result<bool> {
+        let Some(loop_status) = mode.to_mpris() else {
+            bail!("{mode} has no MPRIS LoopStatus");
+        };
+        self.proxy.set_loop_status(loop_status).await?;
+        Ok(true)
+    }
+
+    async fn set_shuffle(&self, active: bool) -> Result<bool> {
+        self.proxy.set_shuffle(active).await?;
+        Ok(true)
+    }
+
+    async fn set_playback_rate(&self, rate: f64) -> Result<bool> {
+        self.proxy.set_rate(rate).await?;
+        Ok(true)
+    }
+}
+
+impl PlatformError for zbus::Error {
+    fn code(&self) -> Option<String> {
+        match self {
+            zbus::Error::MethodError(name, _, _) => Some(name.to_string()),
+            zbus::Error::FDO(error) => Some(DBusError::name(&**error).to_string()),
+            _ => None,
+        }
+    }
+
+    fn message(&self) -> String {
+        match self {
+            zbus::Error::MethodError(_, Some(description), _) => description.clone(),
+            zbus::Error::FDO(error) => DBusError::description(&**error)
+                .unwrap_or_default()
+                .to_string(),
+            error => error.to_string(),
+        }
+    }
+}
+
+/// Reads a property the specification marks optional, taking the errors
+/// players answer for properties they do not implement as its absence.
+fn optional<T>(result: zbus::Result<T>) -> zbus::Result<Option<T>> {
+    match result {
+        Ok(value) => Ok(Some(value)),
+        Err(error)
+            if matches!(
+                error.code().as_deref(),
+                Some(
+                    "org.freedesktop.DBus.Error.UnknownProperty"
+                        | "org.freedesktop.DBus.Error.InvalidArgs"
+                )
+            ) =>
+        {
+            Ok(None)
+        }
+        Err(error) => Err(error),
+    }
+}
+
+fn micros(value: i64) -> Duration {
+    Duration::from_micros(value.max(0) as u64)
+}
+
+fn string(metadata: &HashMap<String, OwnedValue>, key: &str) -> Option<String> {
+    match metadata.get(key).map(|value| &**value) {
+        Some(Value::Str(value)) => Some(value.to_string()),
+        Some(Value::ObjectPath(value)) => Some(value.to_string()),
+        _ => None,
+    }
+}
+
+/// Reads a list of strings, accepting a bare string from players that do not
+/// follow the spec.
+fn string_list(metadata: &HashMap<String, OwnedValue>, key: &str) -> Option<Vec<String>> {
+    match metadata.get(key).map(|value| &**value) {
+        Some(Value::Array(values)) => Some(
+            values
+                .inner()
+                .iter()
+                .filter_map(|value| match value {
+                    Value::Str(value) => Some(value.to_string()),
+                    _ => None,
+                })
+                .collect(),
+        ),
+        Some(Value::Str(value)) => Some(vec![value.to_string()]),
+        _ => None,
+    }
+}
+
+/// Reads an integer of any width; players disagree on the type of
+/// `mpris:length` and `xesam:trackNumber`.
+fn integer(metadata: &HashMap<String, OwnedValue>, key: &str) -> Option<i64> {
+    match metadata.get(key).map(|value| &**value)? {
+        Value::I16(value) => Some(*value as i64),
+        Value::U16(value) => Some(*value as i64),
+        Value::I32(value) => Some(*value as i64),
+        Value::U32(value) => Some(*value as i64),
+        Value::I64(value) => Some(*value),
+        Value::U64(value) => Some(*value as i64),
+        Value::F64(value) => Some(*value as i64),
+        _ => None,
+    }
+}
+
+/// Describes the cover art behind `mpris:artUrl`; only local files and
+/// `data:` URLs can be inspected without fetching.
+fn thumbnail(url: &str) -> Thumbnail {
+    let (content_type, size) = if let Some(path) = file_url_path(url) {
+        let size = path.metadata().ok().map(|metadata| metadata.len());
+        (content_type_for_path(&path).map(str::to_string), size)
+    } else if let Ok(data) = data_url(url) {
+        (data.content_type, Some(data.bytes.len() as u64))
+    } else {
+        (None, None)
+    };
+
+    Thumbnail {
+        content_type,
+        size,
+        url: Some(url.to_string()),
+        ..Default::default()
+    }
+}
+
+/// Reads the cover art behind a `file://` or `data:` URL. Players such as
+/// Spotify point at art on the web, which is not fetched and reads as no
+/// thumbnail.
+fn fetch_art(url: &str) -> Result<Option<ThumbnailData>> {
+    if let Some(path) = file_url_path(url) {
+        let bytes =
+            fs::read(&path).with_context(|| format!("could not read {}", path.display()))?;
+        return Ok(Some(ThumbnailData {
+            content_type: content_type_for_path(&path).map(str::to_string),
+            bytes,
+        }));
+    }
+    if url.starts_with("data:") {
+        return data_url(url).map(Some);
+    }
+    Ok(None)
+}
+
+/// The local path of a `file://` URL, percent-decoded.
+fn file_url_path(url: &str) -> Option<PathBuf> {
+    let path = url.strip_prefix("file://")?;
+    let path = path.strip_prefix("localhost").unwrap_or(path);
+    if !path.starts_with('/') {
+        return None;
+    }
+    let bytes = percent_decode_str(path).collect::<Vec<_>>();
+    Some(PathBuf::from(OsString::from_vec(bytes)))
+}
+
+/// Decodes a `data:[<media type>][;base64],<data>` URL.
+fn data_url(url: &str) -> Result<ThumbnailData> {
+    let data = url
+        .strip_prefix("data:")
+        .ok_or_else(|| anyhow!("not a data: URL"))?;
+    let (header, payload) = data
+        .split_once(',')
+        .ok_or_else(|| anyhow!("data: URL has no ','"))?;
+    let payload = percent_decode_str(payload).collect::<Vec<_>>();
+    let (media_type, bytes) = match header.strip_suffix(";base64") {
+        Some(media_type) => {
+            let payload = payload
+                .into_iter()
+                .filter(|byte| !byte.is_ascii_whitespace())
+                .collect::<Vec<_>>();
+            (media_type, STANDARD.decode(payload)?)
+        }
+        None => (header, payload),
+    };
+    let content_type = media_type.split(';').next().unwrap_or_default();
+
+    Ok(ThumbnailData {
+        content_type: (!content_type.is_empty()).then(|| content_type.to_string()),
+        bytes,
+    })
+}
+
+fn content_type_for_path(path: &Path) -> Option<&'static str> {
+    let extension = path.extension()?.to_str()?;
+    match extension.to_ascii_lowercase().as_str() {
+        "png" => Some("image/png"),
+        "jpg" | "jpeg" => Some("image/jpeg"),
+        "webp" => Some("image/webp"),
+        "gif" => Some("image/gif"),
+        "bmp" => Some("image/bmp"),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::control::{self, SeekOffset};
+    use crate::snapshot::SessionSnapshot;
+    use crate::testbus::{wait_for, PrivateBus};
+    use zbus::interface;
+    use zbus::object_server::SignalEmitter;
+
+    const NAME: &str = "org.mpris.MediaPlayer2.fake";
+    /// The eight bytes of a PNG signature.
+    const ART_URL: &str = "data:image/png;base64,iVBORw0KGgo=";
+
+    /// A player with fixed capabilities; `Play` and `Seek` change its state
+    /// and announce it like a real player.
+    struct FakePlayer {
+        playback_status: String,
+        metadata: HashMap<String, OwnedValue>,
+        position: i64,
+    }
+
+    impl FakePlayer {
+        fn new() -> Self {
+            let mut metadata = HashMap::new();
+            let mut insert = |key: &str, value: Value<'_>| {
+                metadata.insert(key.to_string(), value.try_into().unwrap());
+            };
+            insert("xesam:title", Value::from("Song"));
+            insert("xesam:artist", Value::from(vec!["A", "B"]));
+            insert("xesam:album", Value::from("Album"));
+            insert("mpris:artUrl", Value::from(ART_URL));
+            insert("mpris:length", Value::from(180_000_000i64));
+            Self {
+                playback_status: "Paused".to_string(),
+                metadata,
+                position: 42_000_000,
+            }
+        }
+    }
+
+    #[interface(name = "org.mpris.MediaPlayer2.Player")]
+    impl FakePlayer {
+        async fn play(&mut self, #[zbus(signal_emitter)] emitter: SignalEmitter<'_>) {
+            self.playback_status = "Playing".to_string();
+            let _ = self.playback_status_changed(&emitter).await;
+        }
+
+        async fn seek(&mut self, offset: i64, #[zbus(signal_emitter)] emitter: SignalEmitter<'_>) {
+            self.position += offset;
+            let _ = Self::seeked(&emitter, self.position).await;
+        }
+
+        #[zbus(signal)]
+        async fn seeked(emitter: &SignalEmitter<'_>, position: i64) -> zbus::Result<()>;
+
+        #[zbus(property)]
+        fn playback_status(&self) -> String {
+            self.playback_status.clone()
+        }
+
+        #[zbus(property)]
+        fn loop_status(&self) -> String {
+            "Track".to_string()
+        }
+
+        #[zbus(property)]
+        fn rate(&self) -> f64 {
+            1.0
+        }
+
+        #[zbus(property)]
+        fn minimum_rate(&self) -> f64 {
+            0.5
+        }
+
+        #[zbus(property)]
+        fn maximum_rate(&self) -> f64 {
+            2.0
+        }
+
+        #[zbus(property)]
+        fn shuffle(&self) -> bool {
+            true
+        }
+
+        #[zbus(property)]
+        fn metadata(&self) -> HashMap<String, OwnedValue> {
+            self.metadata.clone()
+        }
+
+        #[zbus(property(emits_changed_signal = "false"))]
+        fn position(&self) -> i64 {
+            self.position
+        }
+
+        #[zbus(property)]
+        fn can_go_next(&self) -> bool {
+            true
+        }
+
+        #[zbus(property)]
+        fn can_go_previous(&self) -> bool {
+            false
+        }
+
+        #[zbus(property)]
+        fn can_play(&self) -> bool {
+            true
+        }
+
+        #[zbus(property)]
+        fn can_pause(&self) -> bool {
+            true
+        }
+
+        #[zbus(property)]
+        fn can_seek(&self) -> bool {
+            true
+        }
+
+        #[zbus(property)]
+        fn can_control(&self) -> bool {
+            true
+        }
+    }
+
+    /// A player with only the required properties, failing to report
+    /// `CanControl`.
+    struct BarePlayer;
+
+    #[interface(name = "org.mpris.MediaPlayer2.Player")]
+    impl BarePlayer {
+        #[zbus(property)]
+        fn playback_status(&self) -> String {
+            "Stopped".to_string()
+        }
+
+        #[zbus(property)]
+        fn minimum_rate(&self) -> f64 {
+            1.0
+        }
+
+        #[zbus(property)]
+        fn maximum_rate(&self) -> f64 {
+            1.0
+        }
+
+        #[zbus(property)]
+        fn metadata(&self) -> HashMap<String, OwnedValue> {
+            HashMap::new()
+        }
+
+        #[zbus(property(emits_changed_signal = "false"))]
+        fn position(&self) -> i64 {
+            0
+        }
+
+        #[zbus(property)]
+        fn can_go_next(&self) -> bool {
+            false
+        }
+
+        #[zbus(property)]
+        fn can_go_previous(&self) -> bool {
+            false
+        }
+
+        #[zbus(property)]
+        fn can_play(&self) -> bool {
+            false
+        }
+
+        #[zbus(property)]
+        fn can_pause(&self) -> bool {
+            false
+        }
+
+        #[zbus(property)]
+        fn can_seek(&self) -> bool {
+            false
+        }
+
+        #[zbus(property)]
+        fn can_control(&self) -> zbus::fdo::Result<bool> {
+            Err(zbus::fdo::Error::Failed("not today".to_string()))
+        }
+    }
+
+    async fn serve(bus: &PrivateBus, player: impl zbus::object_server::Interface) -> Connection {
+        bus.builder()
+            .name(NAME)
+            .unwrap()
+            .serve_at(OBJECT_PATH, player)
+            .unwrap()
+            .build()
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn maps_player_properties() {
+        let Some(bus) = PrivateBus::start() else {
+            return;
+        };
+        let _player = serve(&bus, FakePlayer::new()).await;
+        let backend = MprisBackend::with_connection(bus.connect().await);
+
+        let session = backend.current_session().await.unwrap().unwrap();
+        assert_eq!(session.id(), NAME);
+        let snapshot = SessionSnapshot::collect(session.as_ref()).await;
+        assert!(snapshot.errors.is_empty(), "{:?}", snapshot.errors);
+
+        let media = snapshot.media_properties.unwrap();
+        assert_eq!(media.title.as_deref(), Some("Song"));
+        assert_eq!(media.artist.as_deref(), Some("A, B"));
+        assert_eq!(media.album_title.as_deref(), Some("Album"));
+        let thumbnail = media.thumbnail.unwrap();
+        assert_eq!(thumbnail.url.as_deref(), Some(ART_URL));
+        assert_eq!(thumbnail.content_type.as_deref(), Some("image/png"));
+        assert_eq!(thumbnail.size, Some(8));
+
+        let playback_info = snapshot.playback_info.unwrap();
+        assert_eq!(playback_info.playback_status, Some(PlaybackStatus::Paused));
+        assert_eq!(playback_info.auto_repeat_mode, Some(RepeatMode::Track));
+        assert_eq!(playback_info.is_shuffle_active, Some(true));
+        let controls = playback_info.controls.unwrap();
+        assert_eq!(controls.is_next_enabled, Some(true));
+        assert_eq!(controls.is_previous_enabled, Some(false));
+        assert_eq!(controls.is_playback_rate_enabled, Some(true));
+
+        let timeline = snapshot.timeline_properties.unwrap();
+        assert_eq!(timeline.end_time, Some(Duration::from_secs(180)));
+        assert_eq!(timeline.max_seek_time, Some(Duration::from_secs(180)));
+        assert_eq!(timeline.position, Some(Duration::from_secs(42)));
+    }
+
+    #[test]
+    fn fetches_only_local_art() {
+        let art = fetch_art(ART_URL).unwrap().unwrap();
+        assert_eq!(art.content_type.as_deref(), Some("image/png"));
+        assert_eq!(art.bytes, b"\x89PNG\r\n\x1a\n");
+        let web = "https://i.scdn.co/image/ab67616d0000b273";
+        assert_eq!(fetch_art(web).unwrap(), None);
+        assert!(fetch_art("file:///nonexistent/cover.png").is_err());
+    }
+
+    #[tokio::test]
+    async fn reports_errors_under_the_property_read() {
+        let Some(bus) = PrivateBus::start() else {
+            return;
+        };
+        let _player = serve(&bus, BarePlayer).await;
+        let backend = MprisBackend::with_connection(bus.connect().await);
+
+        let session = backend.session(NAME).await.unwrap().unwrap();
+        let snapshot = SessionSnapshot::collect(session.as_ref()).await;
+        let fields = snapshot
+            .errors
+            .iter()
+            .map(|error| error.field.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(fields, ["playback_info.can_control"]);
+
+        let playback_info = snapshot.playback_info.unwrap();
+        assert_eq!(playback_info.auto_repeat_mode, None);
+        assert_eq!(playback_info.is_shuffle_active, None);
+        assert_eq!(playback_info.playback_rate, None);
+        let controls = playback_info.controls.unwrap();
+        assert_eq!(controls.is_stop_enabled, None);
+        assert_eq!(controls.is_next_enabled, Some(false));
+    }
+
+    #[tokio::test]
+    async fn forwards_calls_and_reports_signals() {
+        let Some(bus) = PrivateBus::start() else {
+            return;
+        };
+        let player = serve(&bus, FakePlayer::new()).await;
+        let backend = MprisBackend::with_connection(bus.connect().await);
+        let mut events = backend.watch().await.unwrap();
+        let session = backend.session(NAME).await.unwrap().unwrap();
+
+        control::send(session.as_ref(), PlaybackCommand::Play)
+            .await
+            .unwrap();
+        wait_for(&mut events, |event| {
+            matches!(event, MediaEvent::PlaybackInfoChanged { .. })
+        })
+        .await;
+
+        let offset = SeekOffset {
+            backward: false,
+            amount: Duration::from_secs(5),
+        };
+        control::seek(session.as_ref(), offset).await.unwrap();
+        let seeked = wait_for(&mut events, |event| {
+            matches!(event, MediaEvent::Seeked { .. })
+        })
+        .await;
+        assert_eq!(
+            seeked,
+            MediaEvent::Seeked {
+                session: NAME.to_string(),
+                position: Duration::from_secs(47),
+            }
+        );
+
+        let fake = player
+            .object_server()
+            .interface::<_, FakePlayer>(OBJECT_PATH)
+            .await
+            .unwrap();
+        fake.get_mut().await.metadata.insert(
+            "xesam:title".to_string(),
+            Value::from("Other").try_into().unwrap(),
+        );
+        fake.get()
+            .await
+            .metadata_changed(fake.signal_emitter())
+            .await
+            .unwrap();
+        wait_for(&mut events, |event| {
+            matches!(event, MediaEvent::MediaPropertiesChanged { .. })
+        })
+        .await;
+        let snapshot = SessionSnapshot::collect(session.as_ref()).await;
+        let title = snapshot.media_properties.and_then(|media| media.title);
+        assert_eq!(title.as_deref(), Some("Other"));
+    }
+}
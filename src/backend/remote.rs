@@ -0,0 +1,452 @@
+//! A backend mirroring the current session of another instance's `serve`,
+//! so that a session only one machine can read, such as a GSMTC session on
+//! Windows, can be watched or bridged on another.
+//!
+//! The session is kept up to date from the WebSocket feed and held in a
+//! [`MockBackend`], which reports the changes to watchers. Thumbnails and
+//! commands go to the HTTP API, so players act on them as they would on
+//! the serving machine.
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use anyhow::{anyhow, bail, Context, Result};
+use async_trait::async_trait;
+use futures_util::StreamExt;
+use http_body_util::{BodyExt, Empty};
+use hyper::body::Bytes;
+use hyper::client::conn::http1;
+use hyper::{header, Method, Request, StatusCode, Uri};
+use hyper_util::rt::TokioIo;
+use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
+use serde_json::Value;
+use tokio::net::TcpStream;
+use tokio::sync::mpsc::UnboundedReceiver;
+use tokio_tungstenite::tungstenite::Message;
+
+use super::mock::{MockBackend, MockSessionState};
+use super::{MediaBackend, MediaEvent, MediaSession};
+use crate::control::{CommandOutcome, PlaybackCommand, SeekOffset};
+use crate::duration;
+use crate::patch::apply_merge_patch;
+use crate::server::feed::FeedMessage;
+use crate::snapshot::{
+    FieldErrors, MediaProperties, PlaybackInfo, RepeatMode, SessionSnapshot, TimelineProperties,
+};
+use crate::thumbnail::ThumbnailData;
+
+/// A backend with at most one session: the one `/sessions/current` of the
+/// server refers to. Watchers are ended when the server goes away.
+pub struct RemoteBackend {
+    mock: MockBackend,
+    client: Arc<Client>,
+}
+
+impl RemoteBackend {
+    /// Connects to the API served at `url`, such as `http://192.168.1.2:7878`,
+    /// and waits for the first snapshot.
+    pub async fn connect(url: &str) -> Result<Self> {
+        let uri = url
+            .parse::<Uri>()
+            .with_context(|| format!("invalid server URL {url:?}"))?;
+        if uri.scheme_str() != Some("http") {
+            bail!("cannot connect to {url}: only http:// servers are supported");
+        }
+        let authority = uri
+            .authority()
+            .ok_or_else(|| anyhow!("server URL {url:?} has no host"))?
+            .to_string();
+
+        // Positions are extrapolated here from the timeline, like for any
+        // other backend, so the feed does not need to tick.
+        let feed = format!("ws://{authority}/sessions/current/feed?tick=0");
+        let (mut socket, _) = tokio_tungstenite::connect_async(feed.as_str())
+            .await
+            .with_context(|| format!("could not open the feed of {url}"))?;
+        let mock = MockBackend::default();
+        let mut mirror = Mirror {
+            mock: mock.clone(),
+            snapshot: Value::Null,
+        };
+        match socket.next().await {
+            Some(Ok(Message::Text(text))) => mirror.apply(serde_json::from_str(&text)?)?,
+            _ => bail!("{url} closed the feed before sending a snapshot"),
+        }
+
+        tokio::spawn(async move {
+            while let Some(Ok(message)) = socket.next().await {
+                let Message::Text(text) = message else {
+                    continue;
+                };
+                let applied = serde_json::from_str(&text)
+                    .map_err(anyhow::Error::from)
+                    .and_then(|message| mirror.apply(message));
+                if let Err(error) = applied {
+                    eprintln!("ignoring feed message: {error:#}");
+                }
+            }
+            mirror.mock.close();
+        });
+
+        Ok(Self {
+            mock,
+            client: Arc::new(Client {
+                authority,
+                art: Mutex::new(None),
+            }),
+        })
+    }
+
+    fn wrap(&self, session: Box<dyn MediaSession>) -> Box<dyn MediaSession> {
+        Box::new(RemoteSession {
+            mirror: session,
+            client: self.client.clone(),
+        })
+    }
+}
+
+#[async_trait]
+impl MediaBackend for RemoteBackend {
+    async fn sessions(&self) -> Result<Vec<Box<dyn MediaSession>>> {
+        let sessions = self.mock.sessions().await?;
+        Ok(sessions
+            .into_iter()
+            .map(|session| self.wrap(session))
+            .collect())
+    }
+
+    async fn current_session(&self) -> Result<Option<Box<dyn MediaSession>>> {
+        let session = self.mock.current_session().await?;
+        Ok(session.map(|session| self.wrap(session)))
+    }
+
+    async fn watch(&self) -> Result<UnboundedReceiver<MediaEvent>> {
+        self.mock.watch().await
+    }
+}
+
+/// Keeps the mock in step with the feed.
+struct Mirror {
+    mock: MockBackend,
+    /// The snapshot patches apply to, as sent.
+    snapshot: Value,
+}
+
+impl Mirror {
+    fn apply(&mut self, message: FeedMessage) -> Result<()> {
+        match message {
+            FeedMessage::Snapshot { snapshot } => {
+                self.snapshot = serde_json::to_value(&snapshot)?;
+                self.replace(snapshot.map(|snapshot| *snapshot))
+            }
+            FeedMessage::Patch { session, patch } => {
+                self.snapshot = apply_merge_patch(&self.snapshot, &patch);
+                let snapshot = serde_json::from_value::<SessionSnapshot>(self.snapshot.clone())?;
+                self.mock.update(&session, |mirrored| *mirrored = snapshot)
+            }
+            FeedMessage::Position { .. } => Ok(()),
+        }
+    }
+
+    /// Makes `snapshot` the only session, updating it in place when the
+    /// feed still follows the same one.
+    fn replace(&self, snapshot: Option<SessionSnapshot>) -> Result<()> {
+        let state = self.mock.state();
+        let previous = state.sessions.first().map(MockSessionState::id);
+        match (previous, snapshot) {
+            (Some(previous), Some(snapshot)) if previous == snapshot.app_user_model_id => {
+                self.mock.update(previous, |mirrored| *mirrored = snapshot)
+            }
+            (previous, snapshot) => {
+                if let Some(previous) = previous {
+                    self.mock.remove_session(previous)?;
+                }
+                if let Some(snapshot) = snapshot {
+                    self.mock.add_session(MockSessionState {
+                        is_current: true,
+                        snapshot,
+                        ..Default::default()
+                    });
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Requests to the HTTP API, one connection each.
+struct Client {
+    /// `host:port` of the server.
+    authority: String,
+    /// The cover art fetched last, with the hash it was fetched for.
+    art: Mutex<Option<(String, ThumbnailData)>>,
+}
+
+impl Client {
+    async fn request(
+        &self,
+        method: Method,
+        path: &str,
+    ) -> Result<(StatusCode, Option<String>, Bytes)> {
+        let stream = TcpStream::connect(self.authority.as_str())
+            .await
+            .with_context(|| format!("could not connect to {}", self.authority))?;
+        let (mut sender, connection) = http1::handshake(TokioIo::new(stream)).await?;
+        tokio::spawn(connection);
+
+        let request = Request::builder()
+            .method(method)
+            .uri(path)
+            .header(header::HOST, self.authority.as_str())
+            .body(Empty::<Bytes>::new())?;
+        let response = sender.send_request(request).await?;
+        let status = response.status();
+        let content_type = response
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string);
+        let body = response.into_body().collect().await?.to_bytes();
+        Ok((status, content_type, body))
+    }
+
+    /// Sends a control request and returns whether the player accepted it.
+    async fn control(
+        &self,
+        id: &str,
+        action: &str,
+        parameter: Option<(&str, String)>,
+    ) -> Result<bool> {
+        let mut path = format!("/sessions/{}/{action}", encode(id));
+        if let Some((name, value)) = parameter {
+            path = format!("{path}?{name}={}", encode(&value));
+        }
+        let (status, _, body) = self.request(Method::POST, &path).await?;
+        if status != StatusCode::OK {
+            bail!("{}", error_message(status, &body));
+        }
+        let outcome = serde_json::from_slice::<CommandOutcome>(&body)?;
+        Ok(outcome.accepted)
+    }
+}
+
+/// The message of an error response, or its status when it has none.
+fn error_message(status: StatusCode, body: &[u8]) -> String {
+    serde_json::from_slice::<Value>(body)
+        .ok()
+        .and_then(|body| body.get("error")?.as_str().map(str::to_string))
+        .unwrap_or_else(|| format!("server answered {status}"))
+}
+
+fn encode(s: &str) -> String {
+    utf8_percent_encode(s, NON_ALPHANUMERIC).to_string()
+}
+
+/// Reads the mirrored session and sends everything else to the server.
+struct RemoteSession {
+    mirror: Box<dyn MediaSession>,
+    client: Arc<Client>,
+}
+
+#[async_trait]
+impl MediaSession for RemoteSession {
+    fn id(&self) -> &str {
+        self.mirror.id()
+    }
+
+    async fn media_properties(&self, errors: &mut FieldErrors) -> Option<MediaProperties> {
+        self.mirror.media_properties(errors).await
+    }
+
+    async fn playback_info(&self, errors: &mut FieldErrors) -> Option<PlaybackInfo> {
+        self.mirror.playback_info(errors).await
+    }
+
+    async fn timeline_properties(&self, errors: &mut FieldErrors) -> Option<TimelineProperties> {
+        self.mirror.timeline_properties(errors).await
+    }
+
+    /// Fetched again only when the mirrored hash changes.
+    async fn thumbnail(&self) -> Result<Option<ThumbnailData>> {
+        let media = self
+            .mirror
+            .media_properties(&mut FieldErrors::new("media_properties"))
+            .await;
+        let Some(thumbnail) = media.and_then(|media| media.thumbnail) else {
+            return Ok(None);
+        };
+        if let (Some(sha256), Some((fetched, data))) = (
+            &thumbnail.sha256,
+            self.client.art.lock().expect("art lock poisoned").as_ref(),
+        ) {
+            if sha256 == fetched {
+                return Ok(Some(data.clone()));
+            }
+        }
+
+        let path = format!("/sessions/{}/thumbnail", encode(self.id()));
+        let (status, content_type, body) = self.client.request(Method::GET, &path).await?;
+        match status {
+            StatusCode::OK => {}
+            StatusCode::NOT_FOUND => return Ok(None),
+            _ => bail!("{}", error_message(status, &body)),
+        }
+        let data = ThumbnailData {
+            content_type,
+            bytes: body.to_vec(),
+        };
+        if let Some(sha256) = thumbnail.sha256 {
+            *self.client.art.lock().expect("art lock poisoned") = Some((sha256, data.clone()));
+        }
+        Ok(Some(data))
+    }
+
+    async fn send_command(&self, command: PlaybackCommand) -> Result<bool> {
+        self.client
+            .control(self.id(), &command.to_string(), None)
+            .await
+    }
+
+    async fn set_position(&self, position: Duration) -> Result<bool> {
+        let position = duration::format(position);
+        self.client
+            .control(self.id(), "position", Some(("position", position)))
+            .await
+    }
+
+    /// Seeks from the position the server estimates, which is closer to
+    /// the player's than the mirrored one.
+    async fn seek(&self, _current: Duration, offset: SeekOffset) -> Result<bool> {
+        self.client
+            .control(self.id(), "seek", Some(("offset", offset.to_string())))
+            .await
+    }
+
+    async fn set_repeat_mode(&self, mode: RepeatMode) -> Result<bool> {
+        self.client
+            .control(self.id(), "repeat", Some(("mode", mode.to_string())))
+            .await
+    }
+
+    async fn set_shuffle(&self, active: bool) -> Result<bool> {
+        let state = if active { "on" } else { "off" };
+        self.client
+            .control(self.id(), "shuffle", Some(("state", state.to_string())))
+            .await
+    }
+
+    async fn set_playback_rate(&self, rate: f64) -> Result<bool> {
+        self.client
+            .control(self.id(), "rate", Some(("rate", rate.to_string())))
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::net::TcpListener;
+    use tokio::time;
+
+    use super::*;
+    use crate::backend::mock::MockState;
+    use crate::server;
+    use crate::snapshot::Thumbnail;
+
+    const PNG: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+    async fn serve(source: &MockBackend) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let backend = Arc::new(source.clone());
+        tokio::spawn(server::serve_on(listener, backend, None, Duration::ZERO));
+        url
+    }
+
+    async fn wait_for(events: &mut UnboundedReceiver<MediaEvent>, wanted: MediaEvent) {
+        let wait = async {
+            while let Some(event) = events.recv().await {
+                if event == wanted {
+                    return;
+                }
+            }
+            panic!("watch ended before {wanted}");
+        };
+        time::timeout(Duration::from_secs(5), wait)
+            .await
+            .expect("event did not arrive");
+    }
+
+    #[tokio::test]
+    async fn mirrors_the_served_session() {
+        let mut session = MockSessionState::new("player");
+        session.is_current = true;
+        session.snapshot.media_properties = Some(MediaProperties {
+            title: Some("Song".to_string()),
+            thumbnail: Some(Thumbnail::default()),
+            ..Default::default()
+        });
+        session.snapshot.timeline_properties = Some(TimelineProperties {
+            end_time: Some(Duration::from_secs(180)),
+            position: Some(Duration::from_secs(10)),
+            ..Default::default()
+        });
+        session.thumbnail = Some(ThumbnailData {
+            content_type: Some("image/png".to_string()),
+            bytes: PNG.to_vec(),
+        });
+        let source = MockBackend::new(MockState {
+            sessions: vec![session],
+        });
+        let remote = RemoteBackend::connect(&serve(&source).await).await.unwrap();
+        let mut events = remote.watch().await.unwrap();
+
+        let mirrored = remote.current_session().await.unwrap().unwrap();
+        let snapshot = SessionSnapshot::collect(mirrored.as_ref()).await;
+        assert_eq!(snapshot.app_user_model_id, "player");
+        let media = snapshot.media_properties.unwrap();
+        assert_eq!(media.title.as_deref(), Some("Song"));
+        let thumbnail = media.thumbnail.unwrap();
+        assert_eq!(
+            thumbnail.detected_content_type.as_deref(),
+            Some("image/png")
+        );
+
+        assert!(mirrored.send_command(PlaybackCommand::Play).await.unwrap());
+        let offset = "-5s".parse::<SeekOffset>().unwrap();
+        assert!(mirrored
+            .set_position(Duration::from_secs(30))
+            .await
+            .unwrap());
+        assert!(mirrored.seek(Duration::ZERO, offset).await.unwrap());
+        let calls = source.calls("player");
+        assert_eq!(calls[..2], ["play", "position 00:30.000"]);
+        assert!(calls[2].starts_with("position 00:25."), "{calls:?}");
+
+        source
+            .update("player", |snapshot| {
+                snapshot.media_properties.as_mut().unwrap().title = Some("Other".to_string());
+            })
+            .unwrap();
+        let changed = MediaEvent::MediaPropertiesChanged {
+            session: "player".to_string(),
+        };
+        wait_for(&mut events, changed).await;
+        let snapshot = SessionSnapshot::collect(mirrored.as_ref()).await;
+        let title = snapshot.media_properties.unwrap().title;
+        assert_eq!(title.as_deref(), Some("Other"));
+
+        source.remove_session("player").unwrap();
+        let removed = MediaEvent::SessionRemoved {
+            session: "player".to_string(),
+        };
+        wait_for(&mut events, removed).await;
+        assert!(remote.current_session().await.unwrap().is_none());
+        assert!(mirrored.send_command(PlaybackCommand::Play).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn refuses_other_schemes() {
+        let error = RemoteBackend::connect("https://example.com").await;
+        assert!(error.is_err());
+    }
+}
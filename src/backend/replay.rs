@@ -0,0 +1,247 @@
+//! A backend playing a recorded [`Trace`] back.
+//!
+//! Sessions start out as the trace header has them and change with each
+//! record at its recorded time, divided by the replay speed. Watchers get
+//! the recorded events, and sessions read what was recorded along with
+//! them, so everything above the backend runs as it did live. Commands are
+//! applied like on a [`MockBackend`] until the next record overrides them.
+
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+use anyhow::{bail, Result};
+use async_trait::async_trait;
+use tokio::sync::mpsc::UnboundedReceiver;
+use tokio::time::{self, Instant};
+
+use super::mock::{MockBackend, MockSessionState, MockState};
+use super::{universal_time, MediaBackend, MediaEvent, MediaSession};
+use crate::events::EventRecord;
+use crate::trace::Trace;
+
+/// A backend replaying a trace, starting with the first watch subscription
+/// and ending every subscription after the last record.
+pub struct ReplayBackend {
+    mock: MockBackend,
+    /// Records still to play; taken when playback starts.
+    records: Mutex<Option<Vec<EventRecord>>>,
+    /// When the trace was recorded, to move timeline anchors to replay time.
+    recorded_at: Option<i64>,
+    speed: f64,
+}
+
+impl ReplayBackend {
+    /// Replays `trace` at `speed` times the recorded pace.
+    pub fn new(trace: Trace, speed: f64) -> Result<Self> {
+        if !(speed.is_finite() && speed > 0.0) {
+            bail!("replay speed must be a positive number, not {speed}");
+        }
+        let recorded_at = trace.header.as_ref().map(|header| header.recorded_at);
+        let mut state = trace.header.map(|header| header.state).unwrap_or_default();
+        if let Some(recorded_at) = recorded_at {
+            let clock = Clock::new(recorded_at, speed);
+            for session in &mut state.sessions {
+                clock.rebase(session);
+            }
+        }
+        Ok(Self {
+            mock: MockBackend::new(state),
+            records: Mutex::new(Some(trace.records)),
+            recorded_at,
+            speed,
+        })
+    }
+
+    /// Replays the trace file at `path`, see [`Trace::load`].
+    pub fn load(path: &Path, speed: f64) -> Result<Self> {
+        Self::new(Trace::load(path)?, speed)
+    }
+
+    /// The backend holding the replayed sessions.
+    pub fn mock(&self) -> &MockBackend {
+        &self.mock
+    }
+
+    fn start(&self) {
+        let records = self
+            .records
+            .lock()
+            .expect("replay records lock poisoned")
+            .take();
+        let Some(records) = records else {
+            return;
+        };
+        let clock = self
+            .recorded_at
+            .map(|recorded_at| Clock::new(recorded_at, self.speed));
+        tokio::spawn(play(self.mock.clone(), records, clock, self.speed));
+    }
+}
+
+#[async_trait]
+impl MediaBackend for ReplayBackend {
+    async fn sessions(&self) -> Result<Vec<Box<dyn MediaSession>>> {
+        self.mock.sessions().await
+    }
+
+    async fn current_session(&self) -> Result<Option<Box<dyn MediaSession>>> {
+        self.mock.current_session().await
+    }
+
+    /// Subscribes before playback starts, so the first watcher sees every
+    /// record.
+    async fn watch(&self) -> Result<UnboundedReceiver<MediaEvent>> {
+        let events = self.mock.watch().await?;
+        self.start();
+        Ok(events)
+    }
+}
+
+async fn play(mock: MockBackend, records: Vec<EventRecord>, clock: Option<Clock>, speed: f64) {
+    let started = Instant::now();
+    for record in records {
+        time::sleep_until(started + record.timestamp.div_f64(speed)).await;
+        apply(&mock, &record, clock.as_ref());
+    }
+    mock.close();
+}
+
+/// Brings the state to where it was right after `record` and reports its
+/// event.
+fn apply(mock: &MockBackend, record: &EventRecord, clock: Option<&Clock>) {
+    let change = |state: &mut MockState| {
+        if let MediaEvent::SessionRemoved { session } = &record.event {
+            state.sessions.retain(|existing| existing.id() != session);
+            return;
+        }
+        if let Some(id) = record.event.session() {
+            let index = match state.sessions.iter().position(|session| session.id() == id) {
+                Some(index) => index,
+                None => {
+                    state.sessions.push(MockSessionState::new(id));
+                    state.sessions.len() - 1
+                }
+            };
+            let session = &mut state.sessions[index];
+            record.apply(&mut session.snapshot);
+            if let Some(clock) = clock {
+                clock.rebase(session);
+            }
+        }
+        if let MediaEvent::CurrentSessionChanged { session } = &record.event {
+            for existing in &mut state.sessions {
+                existing.is_current = Some(existing.id()) == session.as_deref();
+            }
+        }
+    };
+    mock.script(change, [record.event.clone()]);
+}
+
+/// Maps recorded wall-clock times to replay time.
+struct Clock {
+    recorded_at: i64,
+    started: i64,
+    speed: f64,
+}
+
+impl Clock {
+    fn new(recorded_at: i64, speed: f64) -> Self {
+        Self {
+            recorded_at,
+            started: universal_time(SystemTime::now()),
+            speed,
+        }
+    }
+
+    /// Moves the timeline anchor of `session`, so positions are
+    /// extrapolated from the replayed update rather than the recorded one.
+    fn rebase(&self, session: &mut MockSessionState) {
+        let updated = session
+            .snapshot
+            .timeline_properties
+            .as_mut()
+            .and_then(|timeline| timeline.last_updated_time.as_mut());
+        if let Some(updated) = updated {
+            let since = (*updated - self.recorded_at) as f64 / self.speed;
+            *updated = self.started + since as i64;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::snapshot::SessionList;
+
+    const TRACE: &str = r#"
+{"recorded_at":133000000000000000,"sessions":[{"is_current":true,"app_user_model_id":"player","media_properties":{"title":"First","genres":[]},"playback_info":null,"timeline_properties":{"position":0,"last_updated_time":133000000000000000}}]}
+{"timestamp":1000000,"kind":"media_properties_changed","session":"player","media_properties":{"title":"Second","genres":[]},"errors":[{"field":"media_properties.artist","message":"gone"}]}
+{"timestamp":2000000,"kind":"session_added","session":"other","media_properties":{"title":"Other","genres":[]}}
+{"timestamp":3000000,"kind":"current_session_changed","session":"other","media_properties":{"title":"Other","genres":[]}}
+{"timestamp":4000000,"kind":"session_added","session":"gone"}
+{"timestamp":5000000,"kind":"session_removed","session":"gone"}
+"#;
+
+    fn titles(list: &SessionList) -> Vec<(bool, String)> {
+        list.sessions
+            .iter()
+            .map(|session| {
+                let title = session
+                    .snapshot
+                    .media_properties
+                    .as_ref()
+                    .and_then(|media| media.title.clone())
+                    .unwrap_or_default();
+                (session.is_current, title)
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn replays_records_in_order() {
+        let trace = Trace::parse(TRACE).unwrap();
+        let backend = ReplayBackend::new(trace, 100.0).unwrap();
+        let before = SessionList::collect(&backend).await.unwrap();
+        assert_eq!(titles(&before), [(true, "First".to_string())]);
+
+        let mut events = backend.watch().await.unwrap();
+        let mut replayed = vec![];
+        while let Some(event) = events.recv().await {
+            replayed.push(event.to_string());
+        }
+        assert_eq!(replayed.len(), 5);
+
+        let after = SessionList::collect(&backend).await.unwrap();
+        assert_eq!(
+            titles(&after),
+            [(false, "Second".to_string()), (true, "Other".to_string())]
+        );
+        assert_eq!(after.sessions[0].snapshot.errors.len(), 1);
+        assert_eq!(
+            after.sessions[0].snapshot.errors[0].field,
+            "media_properties.artist"
+        );
+    }
+
+    #[test]
+    fn moves_timeline_anchors_to_replay_time() {
+        let trace = Trace::parse(TRACE).unwrap();
+        let before = universal_time(SystemTime::now());
+        let backend = ReplayBackend::new(trace, 1.0).unwrap();
+        let state = backend.mock().state();
+        let updated = state.sessions[0]
+            .snapshot
+            .timeline_properties
+            .as_ref()
+            .and_then(|timeline| timeline.last_updated_time)
+            .unwrap();
+        assert!(updated >= before);
+    }
+
+    #[test]
+    fn rejects_invalid_speed() {
+        assert!(ReplayBackend::new(Trace::default(), 0.0).is_err());
+        assert!(ReplayBackend::new(Trace::default(), f64::NAN).is_err());
+    }
+}
@@ -0,0 +1,899 @@
+//! Publishing a session as an MPRIS2 player, for tools that only speak MPRIS.
+//!
+//! The bridge follows a session of any backend the way `watch` does and
+//! mirrors it on the session bus. Calls to the published player are
+//! forwarded to the followed session through [`crate::control`]. With
+//! [`crate::backend::remote`], that includes a GSMTC session served from a
+//! Windows machine.
+
+use std::collections::HashMap;
+use std::env;
+use std::fs::{self, DirBuilder, OpenOptions};
+use std::io::{self, Write};
+use std::os::unix::fs::{DirBuilderExt, MetadataExt, OpenOptionsExt};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+
+use anyhow::Result;
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use zbus::object_server::{InterfaceRef, SignalEmitter};
+use zbus::zvariant::{ObjectPath, OwnedValue, Value};
+use zbus::{connection, fdo, interface};
+
+use crate::backend::{MediaBackend, MediaEvent, MediaSession, MPRIS_BUS_NAME_PREFIX};
+use crate::control::{self, CommandOutcome, PlaybackCommand, SeekOffset};
+use crate::position::PositionEstimator;
+use crate::selector::SessionSelector;
+use crate::snapshot::{PlaybackControls, RepeatMode, SessionSnapshot, Thumbnail};
+
+const OBJECT_PATH: &str = "/org/mpris/MediaPlayer2";
+
+/// Track id MPRIS reserves for "no track".
+const NO_TRACK: &str = "/org/mpris/MediaPlayer2/TrackList/NoTrack";
+
+/// Rates offered while the source allows changing the rate; snapshots do
+/// not carry the range a player supports.
+const RATE_RANGE: (f64, f64) = (0.25, 4.0);
+
+/// How far a newly reported position may be from the extrapolated one
+/// before it is announced as a seek.
+const SEEK_THRESHOLD: Duration = Duration::from_secs(1);
+
+/// Characters left as they are in the `file://` URLs of saved cover art.
+const PATH_SET: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'/')
+    .remove(b'-')
+    .remove(b'_')
+    .remove(b'.');
+
+/// Publishes the session `selector` picks, or the current one, as
+/// `org.mpris.MediaPlayer2.<name>` until the backend stops reporting events.
+pub async fn run(
+    backend: Arc<dyn MediaBackend>,
+    selector: Option<SessionSelector>,
+    name: &str,
+) -> Result<()> {
+    run_on(connection::Builder::session()?, backend, selector, name).await
+}
+
+/// Like [`run`], publishing on the bus `bus` connects to.
+pub async fn run_on(
+    bus: connection::Builder<'_>,
+    backend: Arc<dyn MediaBackend>,
+    selector: Option<SessionSelector>,
+    name: &str,
+) -> Result<()> {
+    let bus_name = format!("{MPRIS_BUS_NAME_PREFIX}{name}");
+    let mut events = backend.watch().await?;
+    let source = Arc::new(Source {
+        backend,
+        selector,
+        bus_name: bus_name.clone(),
+        state: Mutex::new(State::default()),
+    });
+    let session = source.follow().await;
+    source.update(session).await;
+
+    let connection = bus
+        .name(bus_name.as_str())?
+        .serve_at(OBJECT_PATH, Root)?
+        .serve_at(
+            OBJECT_PATH,
+            Player {
+                source: source.clone(),
+            },
+        )?
+        .build()
+        .await?;
+    let player = connection
+        .object_server()
+        .interface::<_, Player>(OBJECT_PATH)
+        .await?;
+    eprintln!("publishing as {bus_name}");
+    let mut following = source.following();
+    report(following.as_deref());
+
+    while let Some(event) = events.recv().await {
+        let moves = match &event {
+            MediaEvent::SessionAdded { .. } | MediaEvent::SessionRemoved { .. } => true,
+            MediaEvent::CurrentSessionChanged { .. } => source.selector.is_none(),
+            _ => false,
+        };
+        let session = if moves {
+            source.follow().await
+        } else if event.session().is_some() && event.session() == following.as_deref() {
+            source.state().session.clone()
+        } else {
+            continue;
+        };
+
+        let update = source.update(session).await;
+        publish(&player, &update).await?;
+        if source.following() != following {
+            following = source.following();
+            report(following.as_deref());
+        }
+    }
+    Ok(())
+}
+
+fn report(following: Option<&str>) {
+    match following {
+        Some(id) => eprintln!("following {id}"),
+        None => eprintln!("no session to follow"),
+    }
+}
+
+/// Emits `PropertiesChanged` for every property `update` changed, and
+/// `Seeked` when the position jumped.
+async fn publish(player: &InterfaceRef<Player>, update: &Update) -> zbus::Result<()> {
+    let emitter = player.signal_emitter();
+    let interface = player.get().await;
+    let (before, after) = (&update.before, &update.after);
+
+    macro_rules! changed {
+        ($($field:ident => $signal:ident),* $(,)?) => {
+            $(
+                if before.$field != after.$field {
+                    interface.$signal(emitter).await?;
+                }
+            )*
+        };
+    }
+    changed! {
+        playback_status => playback_status_changed,
+        loop_status => loop_status_changed,
+        rate => rate_changed,
+        shuffle => shuffle_changed,
+        metadata => metadata_changed,
+        minimum_rate => minimum_rate_changed,
+        maximum_rate => maximum_rate_changed,
+        can_go_next => can_go_next_changed,
+        can_go_previous => can_go_previous_changed,
+        can_play => can_play_changed,
+        can_pause => can_pause_changed,
+        can_seek => can_seek_changed,
+    }
+
+    if let Some(position) = update.seeked {
+        Player::seeked(emitter, micros(position)).await?;
+    }
+    Ok(())
+}
+
+/// The followed session and what is published about it.
+struct Source {
+    backend: Arc<dyn MediaBackend>,
+    selector: Option<SessionSelector>,
+    /// The bridge's own bus name, which an MPRIS backend lists as a session.
+    bus_name: String,
+    state: Mutex<State>,
+}
+
+struct State {
+    session: Option<Arc<dyn MediaSession>>,
+    published: Published,
+    estimator: Option<PositionEstimator>,
+    /// Counts track changes, to give each track its own `mpris:trackid`.
+    track: u64,
+    track_key: Option<TrackKey>,
+    /// Hash and URL of the cover art last saved for players that hand out
+    /// bytes rather than URLs.
+    art: Option<(String, String)>,
+}
+
+/// Title, artist and album; a change in any of them is a new track.
+type TrackKey = (Option<String>, Option<String>, Option<String>);
+
+impl Default for State {
+    fn default() -> Self {
+        Self {
+            session: None,
+            published: Published::new(None, NO_TRACK, None),
+            estimator: None,
+            track: 0,
+            track_key: None,
+            art: None,
+        }
+    }
+}
+
+impl State {
+    fn track_id(&self) -> String {
+        match self.track_key {
+            Some(_) => format!("/io/github/waylyrics/testgsmtc/track/{}", self.track),
+            None => NO_TRACK.to_string(),
+        }
+    }
+}
+
+/// What [`Source::update`] changed.
+struct Update {
+    before: Published,
+    after: Published,
+    /// Where playback jumped to, if it did.
+    seeked: Option<Duration>,
+}
+
+impl Source {
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.state.lock().expect("bridge state lock poisoned")
+    }
+
+    fn following(&self) -> Option<String> {
+        let state = self.state();
+        state
+            .session
+            .as_ref()
+            .map(|session| session.id().to_string())
+    }
+
+    fn session(&self) -> fdo::Result<Arc<dyn MediaSession>> {
+        self.state()
+            .session
+            .clone()
+            .ok_or_else(|| fdo::Error::Failed("no session to forward to".to_string()))
+    }
+
+    /// The session to mirror: the first one `selector` matches, or else the
+    /// current one, never the bridge itself. When the backend considers the
+    /// bridge current, as an MPRIS backend may, the session mirrored so far
+    /// is kept if it is still there.
+    async fn follow(&self) -> Option<Arc<dyn MediaSession>> {
+        if let Some(selector) = &self.selector {
+            return self
+                .backend
+                .sessions()
+                .await
+                .ok()?
+                .into_iter()
+                .find(|session| session.id() != self.bus_name && selector.matches(session.id()))
+                .map(Arc::from);
+        }
+        let current = self.backend.current_session().await.ok().flatten();
+        if let Some(current) = current.filter(|current| current.id() != self.bus_name) {
+            return Some(Arc::from(current));
+        }
+
+        let following = self.following();
+        let others = self
+            .backend
+            .sessions()
+            .await
+            .ok()?
+            .into_iter()
+            .filter(|session| session.id() != self.bus_name)
+            .collect::<Vec<_>>();
+        let index = others
+            .iter()
+            .position(|session| Some(session.id()) == following.as_deref())
+            .unwrap_or(0);
+        others.into_iter().nth(index).map(Arc::from)
+    }
+
+    /// Reads `session` and makes it the published one.
+    async fn update(&self, session: Option<Arc<dyn MediaSession>>) -> Update {
+        let snapshot = match &session {
+            Some(session) => Some(SessionSnapshot::collect(session.as_ref()).await),
+            None => None,
+        };
+        let media = snapshot
+            .as_ref()
+            .and_then(|snapshot| snapshot.media_properties.as_ref());
+        let art_url = match (&session, media.and_then(|media| media.thumbnail.as_ref())) {
+            (Some(session), Some(thumbnail)) => self.art_url(session.as_ref(), thumbnail).await,
+            _ => None,
+        };
+        let estimator = snapshot.as_ref().and_then(|snapshot| {
+            let timeline = snapshot.timeline_properties.as_ref()?;
+            PositionEstimator::new(timeline, snapshot.playback_info.as_ref())
+        });
+
+        let mut state = self.state();
+        let same_session = match (&session, &state.session) {
+            (Some(new), Some(old)) => new.id() == old.id(),
+            _ => false,
+        };
+        let seeked = match (state.estimator, estimator) {
+            (Some(old), Some(new)) if same_session => {
+                let (expected, reported) = (old.now(), new.now());
+                let drift = expected.max(reported) - expected.min(reported);
+                (drift > SEEK_THRESHOLD).then_some(reported)
+            }
+            _ => None,
+        };
+
+        let track_key = media.map(|media| {
+            (
+                media.title.clone(),
+                media.artist.clone(),
+                media.album_title.clone(),
+            )
+        });
+        if track_key.is_some() && track_key != state.track_key {
+            state.track += 1;
+        }
+        state.track_key = track_key;
+
+        let after = Published::new(snapshot.as_ref(), &state.track_id(), art_url);
+        let before = std::mem::replace(&mut state.published, after.clone());
+        state.session = session;
+        state.estimator = estimator;
+        Update {
+            before,
+            after,
+            seeked,
+        }
+    }
+
+    /// The thumbnail's own URL, or a `file://` URL of its bytes saved under
+    /// [`art_dir`]. Each cover is saved once.
+    async fn art_url(&self, session: &dyn MediaSession, thumbnail: &Thumbnail) -> Option<String> {
+        if let Some(url) = &thumbnail.url {
+            return Some(url.clone());
+        }
+        let sha256 = thumbnail.sha256.as_ref()?;
+        if let Some((saved, url)) = &self.state().art {
+            if saved == sha256 {
+                return Some(url.clone());
+            }
+        }
+
+        let data = session.thumbnail().await.ok()??;
+        let extension = data.extension().unwrap_or("bin");
+        let path = art_dir().ok()?.join(format!("{sha256}.{extension}"));
+        write_private(&path, &data.bytes).ok()?;
+        let url = file_url(&path);
+        self.state().art = Some((sha256.clone(), url.clone()));
+        Some(url)
+    }
+}
+
+/// Where cover art is saved: `$XDG_RUNTIME_DIR/test-gsmtc`, or else
+/// `test-gsmtc-<uid>` under the temporary directory, which other users can
+/// write to as well.
+fn art_dir() -> io::Result<PathBuf> {
+    let dir = match env::var_os("XDG_RUNTIME_DIR") {
+        Some(runtime) if !runtime.is_empty() => PathBuf::from(runtime).join("test-gsmtc"),
+        _ => env::temp_dir().join(format!("test-gsmtc-{}", uid())),
+    };
+    private_dir(&dir)?;
+    Ok(dir)
+}
+
+/// Creates `dir` readable by this user alone, or checks that the one there
+/// is such a directory rather than one another user made or a symlink.
+fn private_dir(dir: &Path) -> io::Result<()> {
+    match DirBuilder::new().mode(0o700).create(dir) {
+        Err(error) if error.kind() != io::ErrorKind::AlreadyExists => return Err(error),
+        _ => {}
+    }
+    let metadata = fs::symlink_metadata(dir)?;
+    if !metadata.is_dir() || metadata.uid() != uid() || metadata.mode() & 0o077 != 0 {
+        return Err(io::Error::other(format!(
+            "{} is not a directory private to this user",
+            dir.display()
+        )));
+    }
+    Ok(())
+}
+
+/// Writes `bytes` to `path` without following a symlink there.
+fn write_private(path: &Path, bytes: &[u8]) -> io::Result<()> {
+    OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .custom_flags(libc::O_NOFOLLOW)
+        .open(path)?
+        .write_all(bytes)
+}
+
+fn uid() -> libc::uid_t {
+    // SAFETY: getuid has no preconditions and cannot fail.
+    unsafe { libc::getuid() }
+}
+
+fn file_url(path: &Path) -> String {
+    let path = path.to_string_lossy();
+    format!("file://{}", utf8_percent_encode(&path, PATH_SET))
+}
+
+fn micros(duration: Duration) -> i64 {
+    duration.as_micros().try_into().unwrap_or(i64::MAX)
+}
+
+/// The `Player` property values last published.
+#[derive(Debug, Clone, PartialEq)]
+struct Published {
+    playback_status: &'static str,
+    loop_status: &'static str,
+    rate: f64,
+    shuffle: bool,
+    metadata: HashMap<String, OwnedValue>,
+    minimum_rate: f64,
+    maximum_rate: f64,
+    can_go_next: bool,
+    can_go_previous: bool,
+    can_play: bool,
+    can_pause: bool,
+    can_seek: bool,
+}
+
+impl Published {
+    /// Controls the source does not report are offered; the forwarded call
+    /// fails if the player turns it down.
+    fn new(snapshot: Option<&SessionSnapshot>, track_id: &str, art_url: Option<String>) -> Self {
+        let playback_info = snapshot.and_then(|snapshot| snapshot.playback_info.as_ref());
+        let controls = playback_info.and_then(|playback_info| playback_info.controls.as_ref());
+        let can = |enabled: fn(&PlaybackControls) -> Option<bool>| {
+            snapshot.is_some() && controls.and_then(enabled) != Some(false)
+        };
+        let rate = playback_info
+            .and_then(|playback_info| playback_info.playback_rate)
+            .unwrap_or(1.0);
+        let rate_range = match can(|controls| controls.is_playback_rate_enabled) {
+            true => (RATE_RANGE.0.min(rate), RATE_RANGE.1.max(rate)),
+            false => (rate, rate),
+        };
+
+        Self {
+            playback_status: playback_info
+                .and_then(|playback_info| playback_info.playback_status)
+                .map_or("Stopped", |status| status.to_mpris()),
+            loop_status: playback_info
+                .and_then(|playback_info| playback_info.auto_repeat_mode)
+                .and_then(RepeatMode::to_mpris)
+                .unwrap_or("None"),
+            rate,
+            shuffle: playback_info
+                .and_then(|playback_info| playback_info.is_shuffle_active)
+                .unwrap_or(false),
+            metadata: metadata(snapshot, track_id, art_url),
+            minimum_rate: rate_range.0,
+            maximum_rate: rate_range.1,
+            can_go_next: can(|controls| controls.is_next_enabled),
+            can_go_previous: can(|controls| controls.is_previous_enabled),
+            can_play: can(|controls| controls.is_play_enabled),
+            can_pause: can(|controls| controls.is_pause_enabled),
+            can_seek: can(|controls| controls.is_playback_position_enabled),
+        }
+    }
+}
+
+/// The `Metadata` map for `snapshot`, using the `xesam:` keys MPRIS players
+/// commonly fill in.
+fn metadata(
+    snapshot: Option<&SessionSnapshot>,
+    track_id: &str,
+    art_url: Option<String>,
+) -> HashMap<String, OwnedValue> {
+    let mut metadata = HashMap::new();
+    let track_id = ObjectPath::try_from(track_id).expect("track ids are valid object paths");
+    insert(&mut metadata, "mpris:trackid", track_id);
+    let Some(snapshot) = snapshot else {
+        return metadata;
+    };
+
+    if let Some(media) = &snapshot.media_properties {
+        if let Some(title) = &media.title {
+            insert(&mut metadata, "xesam:title", title.as_str());
+        }
+        if let Some(artist) = &media.artist {
+            insert(&mut metadata, "xesam:artist", vec![artist.as_str()]);
+        }
+        if let Some(album) = &media.album_title {
+            insert(&mut metadata, "xesam:album", album.as_str());
+        }
+        if let Some(album_artist) = &media.album_artist {
+            insert(
+                &mut metadata,
+                "xesam:albumArtist",
+                vec![album_artist.as_str()],
+            );
+        }
+        if !media.genres.is_empty() {
+            insert(&mut metadata, "xesam:genre", media.genres.clone());
+        }
+        if let Some(track_number) = media.track_number {
+            insert(&mut metadata, "xesam:trackNumber", track_number);
+        }
+    }
+    if let Some(art_url) = art_url {
+        insert(&mut metadata, "mpris:artUrl", art_url);
+    }
+
+    let length = snapshot.timeline_properties.as_ref().and_then(|timeline| {
+        let end = timeline.end_time.filter(|end| !end.is_zero())?;
+        end.checked_sub(timeline.start_time.unwrap_or(Duration::ZERO))
+    });
+    if let Some(length) = length {
+        insert(&mut metadata, "mpris:length", micros(length));
+    }
+    metadata
+}
+
+fn insert<'a>(metadata: &mut HashMap<String, OwnedValue>, key: &str, value: impl Into<Value<'a>>) {
+    if let Ok(value) = OwnedValue::try_from(value.into()) {
+        metadata.insert(key.to_string(), value);
+    }
+}
+
+/// Turns the outcome of a forwarded call into the D-Bus reply.
+fn check(result: Result<CommandOutcome>) -> fdo::Result<CommandOutcome> {
+    let outcome = result.map_err(|e| fdo::Error::Failed(format!("{e:#}")))?;
+    if !outcome.succeeded() {
+        return Err(fdo::Error::Failed(format!(
+            "{} did not carry out {}",
+            outcome.session, outcome.command
+        )));
+    }
+    Ok(outcome)
+}
+
+/// `org.mpris.MediaPlayer2`: the bridge cannot be raised or quit remotely.
+struct Root;
+
+#[interface(name = "org.mpris.MediaPlayer2")]
+impl Root {
+    fn raise(&self) {}
+
+    fn quit(&self) {}
+
+    #[zbus(property)]
+    fn can_quit(&self) -> bool {
+        false
+    }
+
+    #[zbus(property)]
+    fn can_raise(&self) -> bool {
+        false
+    }
+
+    #[zbus(property)]
+    fn has_track_list(&self) -> bool {
+        false
+    }
+
+    #[zbus(property)]
+    fn identity(&self) -> String {
+        "test-gsmtc bridge".to_string()
+    }
+
+    #[zbus(property)]
+    fn supported_uri_schemes(&self) -> Vec<String> {
+        vec![]
+    }
+
+    #[zbus(property)]
+    fn supported_mime_types(&self) -> Vec<String> {
+        vec![]
+    }
+}
+
+/// `org.mpris.MediaPlayer2.Player`, read from [`Published`] and forwarded
+/// to the followed session.
+struct Player {
+    source: Arc<Source>,
+}
+
+impl Player {
+    fn published(&self) -> Published {
+        self.source.state().published.clone()
+    }
+
+    async fn send(&self, command: PlaybackCommand) -> fdo::Result<()> {
+        let session = self.source.session()?;
+        check(control::send(session.as_ref(), command).await)?;
+        Ok(())
+    }
+}
+
+#[interface(name = "org.mpris.MediaPlayer2.Player")]
+impl Player {
+    async fn next(&self) -> fdo::Result<()> {
+        self.send(PlaybackCommand::Next).await
+    }
+
+    async fn previous(&self) -> fdo::Result<()> {
+        self.send(PlaybackCommand::Previous).await
+    }
+
+    async fn pause(&self) -> fdo::Result<()> {
+        self.send(PlaybackCommand::Pause).await
+    }
+
+    async fn play_pause(&self) -> fdo::Result<()> {
+        self.send(PlaybackCommand::TogglePlayPause).await
+    }
+
+    async fn stop(&self) -> fdo::Result<()> {
+        self.send(PlaybackCommand::Stop).await
+    }
+
+    async fn play(&self) -> fdo::Result<()> {
+        self.send(PlaybackCommand::Play).await
+    }
+
+    async fn seek(&self, offset: i64) -> fdo::Result<()> {
+        let session = self.source.session()?;
+        let offset = SeekOffset {
+            backward: offset < 0,
+            amount: Duration::from_micros(offset.unsigned_abs()),
+        };
+        check(control::seek(session.as_ref(), offset).await)?;
+        Ok(())
+    }
+
+    /// Calls for another track or a negative position are ignored, as the
+    /// specification asks.
+    async fn set_position(&self, track_id: ObjectPath<'_>, position: i64) -> fdo::Result<()> {
+        if position < 0 || self.source.state().track_id() != track_id.as_str() {
+            return Ok(());
+        }
+        let session = self.source.session()?;
+        let position = Duration::from_micros(position.unsigned_abs());
+        check(control::set_position(session.as_ref(), position).await)?;
+        Ok(())
+    }
+
+    fn open_uri(&self, _uri: String) -> fdo::Result<()> {
+        Err(fdo::Error::NotSupported(
+            "the bridge cannot open URIs".to_string(),
+        ))
+    }
+
+    #[zbus(signal)]
+    async fn seeked(emitter: &SignalEmitter<'_>, position: i64) -> zbus::Result<()>;
+
+    #[zbus(property)]
+    fn playback_status(&self) -> String {
+        self.published().playback_status.to_string()
+    }
+
+    #[zbus(property)]
+    fn loop_status(&self) -> String {
+        self.published().loop_status.to_string()
+    }
+
+    // The setters record the value the player confirmed, so that the
+    // `PropertiesChanged` they emit carries it.
+
+    #[zbus(property)]
+    async fn set_loop_status(&mut self, value: String) -> fdo::Result<()> {
+        let mode = RepeatMode::from_mpris(&value)
+            .map_err(|error| fdo::Error::InvalidArgs(error.to_string()))?;
+        let loop_status = mode.to_mpris().expect("MPRIS loop statuses map back");
+        let session = self.source.session()?;
+        check(control::set_repeat_mode(session.as_ref(), mode).await)?;
+        self.source.state().published.loop_status = loop_status;
+        Ok(())
+    }
+
+    #[zbus(property)]
+    fn rate(&self) -> f64 {
+        self.published().rate
+    }
+
+    #[zbus(property)]
+    async fn set_rate(&mut self, value: f64) -> fdo::Result<()> {
+        let session = self.source.session()?;
+        check(control::set_playback_rate(session.as_ref(), value).await)?;
+        self.source.state().published.rate = value;
+        Ok(())
+    }
+
+    #[zbus(property)]
+    fn shuffle(&self) -> bool {
+        self.published().shuffle
+    }
+
+    #[zbus(property)]
+    async fn set_shuffle(&mut self, value: bool) -> fdo::Result<()> {
+        let session = self.source.session()?;
+        check(control::set_shuffle(session.as_ref(), Some(value)).await)?;
+        self.source.state().published.shuffle = value;
+        Ok(())
+    }
+
+    #[zbus(property)]
+    fn metadata(&self) -> HashMap<String, OwnedValue> {
+        self.published().metadata
+    }
+
+    /// The bridge does not control volume.
+    #[zbus(property(emits_changed_signal = "const"))]
+    fn volume(&self) -> f64 {
+        1.0
+    }
+
+    /// Extrapolated from the last reported position; changes are only
+    /// announced through `Seeked`.
+    #[zbus(property(emits_changed_signal = "false"))]
+    fn position(&self) -> i64 {
+        let estimator = self.source.state().estimator;
+        estimator.map_or(0, |estimator| micros(estimator.now()))
+    }
+
+    #[zbus(property)]
+    fn minimum_rate(&self) -> f64 {
+        self.published().minimum_rate
+    }
+
+    #[zbus(property)]
+    fn maximum_rate(&self) -> f64 {
+        self.published().maximum_rate
+    }
+
+    #[zbus(property)]
+    fn can_go_next(&self) -> bool {
+        self.published().can_go_next
+    }
+
+    #[zbus(property)]
+    fn can_go_previous(&self) -> bool {
+        self.published().can_go_previous
+    }
+
+    #[zbus(property)]
+    fn can_play(&self) -> bool {
+        self.published().can_play
+    }
+
+    #[zbus(property)]
+    fn can_pause(&self) -> bool {
+        self.published().can_pause
+    }
+
+    #[zbus(property)]
+    fn can_seek(&self) -> bool {
+        self.published().can_seek
+    }
+
+    /// Calls are accepted even while no session is followed, and fail then.
+    #[zbus(property(emits_changed_signal = "const"))]
+    fn can_control(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::Permissions;
+    use std::os::unix::fs::{symlink, PermissionsExt};
+
+    use super::*;
+    use crate::backend::mock::{MockBackend, MockSessionState, MockState};
+    use crate::backend::mpris::MprisBackend;
+    use crate::snapshot::{MediaProperties, PlaybackInfo, PlaybackStatus, TimelineProperties};
+    use crate::testbus::{wait_for, PrivateBus};
+
+    const BUS_NAME: &str = "org.mpris.MediaPlayer2.test";
+
+    fn source() -> MockBackend {
+        let mut session = MockSessionState::new("player");
+        session.is_current = true;
+        session.snapshot.media_properties = Some(MediaProperties {
+            title: Some("Song".to_string()),
+            ..Default::default()
+        });
+        session.snapshot.playback_info = Some(PlaybackInfo {
+            playback_status: Some(PlaybackStatus::Paused),
+            ..Default::default()
+        });
+        session.snapshot.timeline_properties = Some(TimelineProperties {
+            start_time: Some(Duration::ZERO),
+            end_time: Some(Duration::from_secs(180)),
+            position: Some(Duration::from_secs(10)),
+            ..Default::default()
+        });
+        MockBackend::new(MockState {
+            sessions: vec![session],
+        })
+    }
+
+    /// The published player, once the bridge got its name.
+    async fn published(client: &MprisBackend) -> Box<dyn MediaSession> {
+        for _ in 0..100 {
+            if let Some(session) = client.session(BUS_NAME).await.unwrap() {
+                return session;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        panic!("{BUS_NAME} was not published");
+    }
+
+    #[test]
+    fn saves_art_only_in_a_private_directory() {
+        let base = env::temp_dir().join(format!("test-gsmtc-art-{}", std::process::id()));
+        fs::create_dir_all(&base).unwrap();
+
+        let dir = base.join("art");
+        private_dir(&dir).unwrap();
+        private_dir(&dir).unwrap();
+        assert_eq!(fs::metadata(&dir).unwrap().mode() & 0o777, 0o700);
+        write_private(&dir.join("cover.png"), b"cover").unwrap();
+
+        let shared = base.join("shared");
+        fs::create_dir(&shared).unwrap();
+        fs::set_permissions(&shared, Permissions::from_mode(0o777)).unwrap();
+        let planted = base.join("planted");
+        symlink(&dir, &planted).unwrap();
+        let link = dir.join("link.png");
+        symlink(dir.join("cover.png"), &link).unwrap();
+
+        let shared = private_dir(&shared);
+        let planted = private_dir(&planted);
+        let followed = write_private(&link, b"other");
+        let cover = fs::read(dir.join("cover.png")).unwrap();
+        fs::remove_dir_all(&base).unwrap();
+
+        assert!(shared.is_err());
+        assert!(planted.is_err());
+        assert!(followed.is_err());
+        assert_eq!(cover, b"cover");
+    }
+
+    #[tokio::test]
+    async fn selector_skips_the_bridge_itself() {
+        let mock = source();
+        mock.add_session(MockSessionState::new(BUS_NAME));
+        mock.add_session(MockSessionState::new("org.mpris.MediaPlayer2.vlc"));
+        let source = Source {
+            backend: Arc::new(mock),
+            selector: Some("org.mpris.MediaPlayer2.*".parse().unwrap()),
+            bus_name: BUS_NAME.to_string(),
+            state: Mutex::new(State::default()),
+        };
+        let followed = source.follow().await.unwrap();
+        assert_eq!(followed.id(), "org.mpris.MediaPlayer2.vlc");
+    }
+
+    #[tokio::test]
+    async fn forwards_calls_and_announces_changes() {
+        let Some(bus) = PrivateBus::start() else {
+            return;
+        };
+        let mock = source();
+        let bridge = tokio::spawn(run_on(bus.builder(), Arc::new(mock.clone()), None, "test"));
+        let client = MprisBackend::with_connection(bus.connect().await);
+        let player = published(&client).await;
+        let mut events = client.watch().await.unwrap();
+
+        assert!(player.send_command(PlaybackCommand::Play).await.unwrap());
+        let offset = SeekOffset {
+            backward: false,
+            amount: Duration::from_secs(5),
+        };
+        assert!(player.seek(Duration::ZERO, offset).await.unwrap());
+        assert!(player.set_position(Duration::from_secs(30)).await.unwrap());
+        let calls = mock.calls("player");
+        assert_eq!(calls.len(), 3, "{calls:?}");
+        assert_eq!(calls[0], "play");
+        // Playing since the call before, so a little past 00:15.
+        assert!(calls[1].starts_with("position 00:15."), "{calls:?}");
+        assert_eq!(calls[2], "position 00:30.000");
+
+        mock.update("player", |snapshot| {
+            snapshot.media_properties.as_mut().unwrap().title = Some("Other".to_string());
+        })
+        .unwrap();
+        wait_for(&mut events, |event| {
+            *event
+                == MediaEvent::MediaPropertiesChanged {
+                    session: BUS_NAME.to_string(),
+                }
+        })
+        .await;
+        let snapshot = SessionSnapshot::collect(player.as_ref()).await;
+        let media = snapshot.media_properties.unwrap();
+        assert_eq!(media.title.as_deref(), Some("Other"));
+        let playback_info = snapshot.playback_info.unwrap();
+        assert_eq!(playback_info.playback_status, Some(PlaybackStatus::Playing));
+
+        mock.close();
+        bridge.await.unwrap().unwrap();
+    }
+}
@@ -0,0 +1,409 @@
+//! Sending transport commands and settings to a session.
+
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+
+use anyhow::{anyhow, bail, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::backend::MediaSession;
+use crate::duration;
+use crate::position::PositionEstimator;
+use crate::snapshot::{
+    option_nanos, FieldErrors, PlaybackControls, PlaybackInfo, RepeatMode, TimelineProperties,
+};
+
+/// A transport button, as exposed by `GlobalSystemMediaTransportControlsSession`
+/// and the MPRIS `Player` interface.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PlaybackCommand {
+    Play,
+    Pause,
+    #[serde(rename = "toggle")]
+    TogglePlayPause,
+    Next,
+    Previous,
+    Stop,
+}
+
+impl PlaybackCommand {
+    /// Name of the [`PlaybackControls`] flag that gates the command.
+    pub fn control_name(self) -> &'static str {
+        match self {
+            Self::Play => "is_play_enabled",
+            Self::Pause => "is_pause_enabled",
+            Self::TogglePlayPause => "is_play_pause_toggle_enabled",
+            Self::Next => "is_next_enabled",
+            Self::Previous => "is_previous_enabled",
+            Self::Stop => "is_stop_enabled",
+        }
+    }
+
+    /// Whether `controls` allow the command; `None` when the platform did not say.
+    pub fn is_enabled(self, controls: &PlaybackControls) -> Option<bool> {
+        match self {
+            Self::Play => controls.is_play_enabled,
+            Self::Pause => controls.is_pause_enabled,
+            Self::TogglePlayPause => controls.is_play_pause_toggle_enabled,
+            Self::Next => controls.is_next_enabled,
+            Self::Previous => controls.is_previous_enabled,
+            Self::Stop => controls.is_stop_enabled,
+        }
+    }
+}
+
+impl fmt::Display for PlaybackCommand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Play => "play",
+            Self::Pause => "pause",
+            Self::TogglePlayPause => "toggle",
+            Self::Next => "next",
+            Self::Previous => "previous",
+            Self::Stop => "stop",
+        })
+    }
+}
+
+/// A jump relative to the current position, parsed from a signed
+/// [`duration::parse`] string such as `+10s` or `-1m30s`. Without a sign
+/// the jump is forward.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SeekOffset {
+    pub backward: bool,
+    pub amount: Duration,
+}
+
+impl SeekOffset {
+    /// The position `self` leads to from `from`, or `None` before the start.
+    pub fn apply(self, from: Duration) -> Option<Duration> {
+        if self.backward {
+            from.checked_sub(self.amount)
+        } else {
+            from.checked_add(self.amount)
+        }
+    }
+}
+
+impl FromStr for SeekOffset {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (backward, amount) = match s.strip_prefix('-') {
+            Some(amount) => (true, amount),
+            None => (false, s.strip_prefix('+').unwrap_or(s)),
+        };
+        Ok(Self {
+            backward,
+            amount: duration::parse(amount)?,
+        })
+    }
+}
+
+impl fmt::Display for SeekOffset {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let sign = if self.backward { '-' } else { '+' };
+        write!(f, "{sign}{}", duration::format(self.amount))
+    }
+}
+
+/// What became of a command sent to a session.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CommandOutcome {
+    pub session: String,
+    /// The subcommand, such as `play` or `seek`.
+    pub command: String,
+    /// Where playback was asked to move, for `seek` and `position`.
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "option_nanos"
+    )]
+    pub position: Option<Duration>,
+    /// The value asked for, for `repeat`, `shuffle` and `rate`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub value: Option<String>,
+    /// Whether the player accepted the request. Acceptance does not mean the
+    /// state already changed; watch the session to see the effect.
+    pub accepted: bool,
+    /// The value the player reported after accepting a setter.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub read_back: Option<String>,
+    /// Whether `read_back` matches `value`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub applied: Option<bool>,
+}
+
+impl CommandOutcome {
+    /// Whether the command took effect as far as the player tells.
+    pub fn succeeded(&self) -> bool {
+        self.accepted && self.applied != Some(false)
+    }
+}
+
+/// How often and how long a setter waits for the player to report its
+/// new value.
+const READ_BACK_INTERVAL: Duration = Duration::from_millis(100);
+const READ_BACK_ATTEMPTS: u32 = 10;
+
+/// Sends `command` to `session` after checking that its controls allow it.
+/// Controls the platform does not report are not held against the command.
+pub async fn send(session: &dyn MediaSession, command: PlaybackCommand) -> Result<CommandOutcome> {
+    let action = command.to_string();
+    check_enabled(session, &action, command.control_name(), |controls| {
+        command.is_enabled(controls)
+    })
+    .await?;
+
+    Ok(CommandOutcome {
+        session: session.id().to_string(),
+        command: action,
+        accepted: session.send_command(command).await?,
+        ..Default::default()
+    })
+}
+
+/// Moves playback of `session` by `offset` from where it currently is,
+/// extrapolating the reported position to now while it plays.
+pub async fn seek(session: &dyn MediaSession, offset: SeekOffset) -> Result<CommandOutcome> {
+    let timeline = seekable_timeline(session).await?;
+    let playback_info = session
+        .playback_info(&mut FieldErrors::new("playback_info"))
+        .await;
+    let current = PositionEstimator::new(&timeline, playback_info.as_ref())
+        .ok_or_else(|| anyhow!("{} does not report its position", session.id()))?
+        .now();
+    let target = offset.apply(current).ok_or_else(|| {
+        anyhow!(
+            "cannot seek {offset} from {}: that is before the start",
+            duration::format(current)
+        )
+    })?;
+    check_seek_range(&timeline, target)?;
+
+    Ok(CommandOutcome {
+        session: session.id().to_string(),
+        command: "seek".to_string(),
+        position: Some(target),
+        accepted: session.seek(current, offset).await?,
+        ..Default::default()
+    })
+}
+
+/// Moves playback of `session` to `position` from the start of the track.
+pub async fn set_position(
+    session: &dyn MediaSession,
+    position: Duration,
+) -> Result<CommandOutcome> {
+    let timeline = seekable_timeline(session).await?;
+    check_seek_range(&timeline, position)?;
+
+    Ok(CommandOutcome {
+        session: session.id().to_string(),
+        command: "position".to_string(),
+        position: Some(position),
+        accepted: session.set_position(position).await?,
+        ..Default::default()
+    })
+}
+
+/// Reads the timeline of a session whose controls allow seeking.
+async fn seekable_timeline(session: &dyn MediaSession) -> Result<TimelineProperties> {
+    check_enabled(
+        session,
+        "changing the position",
+        "is_playback_position_enabled",
+        |controls| controls.is_playback_position_enabled,
+    )
+    .await?;
+
+    let mut errors = FieldErrors::new("timeline_properties");
+    let timeline = session.timeline_properties(&mut errors).await;
+    errors.require(timeline)
+}
+
+/// Rejects positions outside `min_seek_time..=max_seek_time`; bounds the
+/// platform does not report are not enforced.
+fn check_seek_range(timeline: &TimelineProperties, target: Duration) -> Result<()> {
+    if let Some(min) = timeline.min_seek_time.filter(|min| target < *min) {
+        bail!(
+            "cannot seek to {}: min_seek_time is {}",
+            duration::format(target),
+            duration::format(min)
+        );
+    }
+    if let Some(max) = timeline.max_seek_time.filter(|max| target > *max) {
+        bail!(
+            "cannot seek to {}: max_seek_time is {}",
+            duration::format(target),
+            duration::format(max)
+        );
+    }
+    Ok(())
+}
+
+/// Sets the repeat mode of `session` and checks that the player applied it.
+pub async fn set_repeat_mode(
+    session: &dyn MediaSession,
+    mode: RepeatMode,
+) -> Result<CommandOutcome> {
+    check_enabled(session, "repeat", "is_repeat_enabled", |controls| {
+        controls.is_repeat_enabled
+    })
+    .await?;
+    let accepted = session.set_repeat_mode(mode).await?;
+    Ok(confirm(session, "repeat", mode, accepted, |info| {
+        info.auto_repeat_mode
+    })
+    .await)
+}
+
+/// Turns shuffle of `session` on or off, or flips it when `active` is
+/// `None`, and checks that the player applied it.
+pub async fn set_shuffle(
+    session: &dyn MediaSession,
+    active: Option<bool>,
+) -> Result<CommandOutcome> {
+    let playback_info = check_enabled(session, "shuffle", "is_shuffle_enabled", |controls| {
+        controls.is_shuffle_enabled
+    })
+    .await?;
+    let active = match active {
+        Some(active) => active,
+        None => !playback_info
+            .and_then(|playback_info| playback_info.is_shuffle_active)
+            .ok_or_else(|| anyhow!("{} does not report whether shuffle is on", session.id()))?,
+    };
+    let accepted = session.set_shuffle(active).await?;
+    Ok(confirm(session, "shuffle", active, accepted, |info| {
+        info.is_shuffle_active
+    })
+    .await)
+}
+
+/// Parses an on/off switch such as a [`set_shuffle`] state.
+pub fn parse_switch(s: &str) -> Result<bool> {
+    match s.to_ascii_lowercase().as_str() {
+        "on" | "true" | "yes" | "1" => Ok(true),
+        "off" | "false" | "no" | "0" => Ok(false),
+        _ => bail!("expected on or off, not {s:?}"),
+    }
+}
+
+/// Sets the playback rate of `session` and checks that the player applied it.
+pub async fn set_playback_rate(session: &dyn MediaSession, rate: f64) -> Result<CommandOutcome> {
+    if !(rate.is_finite() && rate > 0.0) {
+        bail!("playback rate must be a positive number, not {rate}");
+    }
+    check_enabled(
+        session,
+        "changing the rate",
+        "is_playback_rate_enabled",
+        |controls| controls.is_playback_rate_enabled,
+    )
+    .await?;
+    let accepted = session.set_playback_rate(rate).await?;
+    Ok(confirm(session, "rate", rate, accepted, |info| info.playback_rate).await)
+}
+
+/// Reads the playback info of `session`, failing when `control` is reported
+/// as false. Controls the platform does not report are not held against the
+/// action.
+async fn check_enabled(
+    session: &dyn MediaSession,
+    action: &str,
+    control: &str,
+    enabled: impl Fn(&PlaybackControls) -> Option<bool>,
+) -> Result<Option<PlaybackInfo>> {
+    let mut errors = FieldErrors::new("playback_info");
+    let playback_info = session.playback_info(&mut errors).await;
+    let controls = playback_info
+        .as_ref()
+        .and_then(|playback_info| playback_info.controls.as_ref());
+    if controls.and_then(enabled) == Some(false) {
+        bail!(
+            "{} does not allow {action} right now ({control} is false)",
+            session.id()
+        );
+    }
+    Ok(playback_info)
+}
+
+/// Builds the outcome of a setter, polling `session` until `read` reports
+/// `wanted` if the player accepted the request.
+async fn confirm<T: PartialEq + fmt::Display>(
+    session: &dyn MediaSession,
+    command: &str,
+    wanted: T,
+    accepted: bool,
+    read: impl Fn(&PlaybackInfo) -> Option<T>,
+) -> CommandOutcome {
+    let mut outcome = CommandOutcome {
+        session: session.id().to_string(),
+        command: command.to_string(),
+        value: Some(wanted.to_string()),
+        accepted,
+        ..Default::default()
+    };
+    if !accepted {
+        return outcome;
+    }
+
+    let mut reported = None;
+    for attempt in 0..READ_BACK_ATTEMPTS {
+        if attempt > 0 {
+            tokio::time::sleep(READ_BACK_INTERVAL).await;
+        }
+        let mut errors = FieldErrors::new("playback_info");
+        reported = session
+            .playback_info(&mut errors)
+            .await
+            .as_ref()
+            .and_then(&read);
+        if reported.as_ref() == Some(&wanted) {
+            break;
+        }
+    }
+    outcome.applied = Some(reported.as_ref() == Some(&wanted));
+    outcome.read_back = reported.map(|value| value.to_string());
+    outcome
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn offset(s: &str) -> SeekOffset {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn parses_signed_offsets() {
+        let forward = SeekOffset {
+            backward: false,
+            amount: Duration::from_secs(10),
+        };
+        assert_eq!(offset("+10s"), forward);
+        assert_eq!(offset("10s"), forward);
+        assert_eq!(
+            offset("-1m30s"),
+            SeekOffset {
+                backward: true,
+                amount: Duration::from_secs(90),
+            }
+        );
+        assert!("+-10s".parse::<SeekOffset>().is_err());
+        assert!("-".parse::<SeekOffset>().is_err());
+    }
+
+    #[test]
+    fn applies_within_the_track() {
+        let from = Duration::from_secs(20);
+        assert_eq!(offset("+5s").apply(from), Some(Duration::from_secs(25)));
+        assert_eq!(offset("-20s").apply(from), Some(Duration::ZERO));
+        assert_eq!(offset("-21s").apply(from), None);
+        assert_eq!(offset("-1:30").to_string(), "-01:30.000");
+    }
+}
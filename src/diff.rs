@@ -0,0 +1,368 @@
+//! Field-by-field differences between two session snapshots.
+
+use std::path::Path;
+
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::backend::mock::MockState;
+use crate::selector::SessionSelector;
+use crate::snapshot::SessionSnapshot;
+
+/// The sections compared, in the order they are listed.
+const SECTIONS: [&str; 3] = ["media_properties", "playback_info", "timeline_properties"];
+
+/// What differs between two snapshots.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SnapshotDiff {
+    /// Id of the left session.
+    pub left: String,
+    /// Id of the right session.
+    pub right: String,
+    pub changes: Vec<FieldChange>,
+}
+
+/// One value that differs, with `null` for a value one side does not report.
+/// Such a side is left out when serialized, as TOML has no null.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FieldChange {
+    /// Dotted path of the value, e.g. `playback_info.controls.is_next_enabled`.
+    pub field: String,
+    #[serde(default, skip_serializing_if = "Value::is_null")]
+    pub left: Value,
+    #[serde(default, skip_serializing_if = "Value::is_null")]
+    pub right: Value,
+}
+
+impl SnapshotDiff {
+    /// Compares media properties, playback info with its controls, and the
+    /// timeline. Ids and read errors are not compared. A missing section or
+    /// value equals `null`, and lists are compared as a whole.
+    pub fn between(left: &SessionSnapshot, right: &SessionSnapshot) -> Self {
+        let mut changes = vec![];
+        let (left_value, right_value) = (to_value(left), to_value(right));
+        for section in SECTIONS {
+            compare(
+                section,
+                left_value.get(section).unwrap_or(&Value::Null),
+                right_value.get(section).unwrap_or(&Value::Null),
+                &mut changes,
+            );
+        }
+        Self {
+            left: left.app_user_model_id.clone(),
+            right: right.app_user_model_id.clone(),
+            changes,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+}
+
+fn to_value(snapshot: &SessionSnapshot) -> Value {
+    serde_json::to_value(snapshot).expect("snapshots serialize to JSON")
+}
+
+fn compare(field: &str, left: &Value, right: &Value, changes: &mut Vec<FieldChange>) {
+    if left == right {
+        return;
+    }
+    let empty = Value::Object(Map::new());
+    let (Value::Object(left_members), Value::Object(right_members)) =
+        (or_empty(left, &empty), or_empty(right, &empty))
+    else {
+        changes.push(FieldChange {
+            field: field.to_string(),
+            left: left.clone(),
+            right: right.clone(),
+        });
+        return;
+    };
+
+    let mut keys = left_members.keys().collect::<Vec<_>>();
+    keys.extend(
+        right_members
+            .keys()
+            .filter(|key| !left_members.contains_key(*key)),
+    );
+    keys.sort();
+    for key in keys {
+        compare(
+            &format!("{field}.{key}"),
+            left_members.get(key).unwrap_or(&Value::Null),
+            right_members.get(key).unwrap_or(&Value::Null),
+            changes,
+        );
+    }
+}
+
+/// Compares a missing section like one with every value missing.
+fn or_empty<'a>(value: &'a Value, empty: &'a Value) -> &'a Value {
+    if value.is_null() {
+        empty
+    } else {
+        value
+    }
+}
+
+/// Reads a snapshot from a file in the shape `--all` or a single-session
+/// dump prints: the session `selector` matches, or else the only or the
+/// current one.
+pub fn load(path: &Path, selector: Option<&SessionSelector>) -> Result<SessionSnapshot> {
+    let state = MockState::load(path)?;
+    let found = match selector {
+        Some(selector) => state
+            .sessions
+            .iter()
+            .find(|session| selector.matches(session.id())),
+        None if state.sessions.len() == 1 => state.sessions.first(),
+        None => state.sessions.iter().find(|session| session.is_current),
+    };
+    match found {
+        Some(session) => Ok(session.snapshot.clone()),
+        None => match selector {
+            Some(selector) => bail!("no session in {} matches {selector}", path.display()),
+            None => bail!(
+                "{} holds {} sessions and none is current; pick one with --session",
+                path.display(),
+                state.sessions.len()
+            ),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format::{self, Render, TimeStyle};
+    use serde_json::json;
+    use std::fs;
+
+    fn snapshot(value: Value) -> SessionSnapshot {
+        serde_json::from_value(value).unwrap()
+    }
+
+    fn small_diff() -> SnapshotDiff {
+        let left = snapshot(json!({
+            "app_user_model_id": "left",
+            "media_properties": { "title": "Song", "artist": "A", "genres": [] },
+            "playback_info": null,
+            "timeline_properties": { "position": 42000000000u64 }
+        }));
+        let right = snapshot(json!({
+            "app_user_model_id": "right",
+            "media_properties": { "title": "Other", "genres": [] },
+            "playback_info": null,
+            "timeline_properties": { "position": 61500000000u64 }
+        }));
+        SnapshotDiff::between(&left, &right)
+    }
+
+    fn render(write: impl FnOnce(&mut Vec<u8>) -> std::io::Result<()>) -> String {
+        let mut output = vec![];
+        write(&mut output).unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn lists_changed_fields() {
+        let left = snapshot(json!({
+            "app_user_model_id": "left",
+            "media_properties": { "title": "Song", "artist": "A", "genres": ["Rock"] },
+            "playback_info": {
+                "playback_status": "Playing",
+                "controls": { "is_next_enabled": true, "is_pause_enabled": true }
+            },
+            "timeline_properties": { "position": 42000000000u64 }
+        }));
+        let right = snapshot(json!({
+            "app_user_model_id": "right",
+            "media_properties": { "title": "Song", "genres": ["Rock", "Pop"] },
+            "playback_info": {
+                "playback_status": "Playing",
+                "controls": { "is_next_enabled": false, "is_pause_enabled": true }
+            },
+            "timeline_properties": null
+        }));
+
+        let diff = SnapshotDiff::between(&left, &right);
+        let fields = diff
+            .changes
+            .iter()
+            .map(|change| change.field.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            fields,
+            [
+                "media_properties.artist",
+                "media_properties.genres",
+                "playback_info.controls.is_next_enabled",
+                "timeline_properties.position",
+            ]
+        );
+        assert_eq!(diff.changes[0].right, Value::Null);
+        assert_eq!(diff.changes[3].left, json!(42000000000u64));
+    }
+
+    #[test]
+    fn equal_snapshots_have_no_changes() {
+        let left = snapshot(json!({
+            "app_user_model_id": "left",
+            "media_properties": { "title": "Song", "artist": null, "genres": [] },
+            "playback_info": null,
+            "timeline_properties": null,
+            "errors": [{ "field": "playback_info", "message": "unavailable" }]
+        }));
+        let right = snapshot(json!({
+            "app_user_model_id": "right",
+            "media_properties": { "title": "Song", "genres": [] },
+            "playback_info": null,
+            "timeline_properties": null
+        }));
+        assert!(SnapshotDiff::between(&left, &right).is_empty());
+    }
+
+    #[test]
+    fn renders_text() {
+        let diff = small_diff();
+        assert_eq!(
+            render(|w| diff.write_text(w, TimeStyle::Raw)),
+            r#"--- "left"
++++ "right"
+media_properties.artist: "A" -> (none)
+media_properties.title: "Song" -> "Other"
+timeline_properties.position: 42000000000 -> 61500000000
+"#
+        );
+        assert_eq!(
+            render(|w| diff.write_text(w, TimeStyle::Human)),
+            r#"--- "left"
++++ "right"
+media_properties.artist: "A" -> (none)
+media_properties.title: "Song" -> "Other"
+timeline_properties.position: 00:42.000 -> 01:01.500
+"#
+        );
+    }
+
+    #[test]
+    fn renders_no_differences() {
+        let diff = SnapshotDiff::between(&SessionSnapshot::default(), &SessionSnapshot::default());
+        assert_eq!(
+            render(|w| diff.write_text(w, TimeStyle::Raw)),
+            "--- \"\"\n+++ \"\"\nno differences\n"
+        );
+    }
+
+    #[test]
+    fn renders_json_without_missing_sides() {
+        let diff = small_diff();
+        let output = render(|w| format::write_json(w, &diff));
+        assert_eq!(
+            serde_json::from_str::<Value>(&output).unwrap(),
+            json!({
+                "left": "left",
+                "right": "right",
+                "changes": [
+                    { "field": "media_properties.artist", "left": "A" },
+                    { "field": "media_properties.title", "left": "Song", "right": "Other" },
+                    {
+                        "field": "timeline_properties.position",
+                        "left": 42000000000u64,
+                        "right": 61500000000u64
+                    }
+                ]
+            })
+        );
+        let parsed = serde_json::from_str::<SnapshotDiff>(&output).unwrap();
+        assert_eq!(parsed.changes, diff.changes);
+    }
+
+    #[test]
+    fn renders_yaml() {
+        let diff = small_diff();
+        assert_eq!(
+            render(|w| format::write_yaml(w, &diff)),
+            "left: left
+right: right
+changes:
+- field: media_properties.artist
+  left: A
+- field: media_properties.title
+  left: Song
+  right: Other
+- field: timeline_properties.position
+  left: 42000000000
+  right: 61500000000
+"
+        );
+    }
+
+    #[test]
+    fn renders_toml_with_a_missing_side() {
+        let diff = small_diff();
+        assert_eq!(
+            render(|w| format::write_toml(w, &diff)),
+            r#"left = "left"
+right = "right"
+
+[[changes]]
+field = "media_properties.artist"
+left = "A"
+
+[[changes]]
+field = "media_properties.title"
+left = "Song"
+right = "Other"
+
+[[changes]]
+field = "timeline_properties.position"
+left = 42000000000
+right = 61500000000
+"#
+        );
+    }
+
+    #[test]
+    fn loads_the_selected_or_current_session() {
+        let dir = std::env::temp_dir().join(format!("test-gsmtc-diff-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let list = dir.join("sessions.json");
+        fs::write(
+            &list,
+            r#"{"sessions": [
+                {"app_user_model_id": "org.mpris.MediaPlayer2.first"},
+                {"is_current": true, "app_user_model_id": "second"}
+            ]}"#,
+        )
+        .unwrap();
+        let single = dir.join("session.json");
+        fs::write(&single, r#"{"app_user_model_id": "solo"}"#).unwrap();
+        let no_current = dir.join("none.json");
+        fs::write(
+            &no_current,
+            r#"{"sessions": [{"app_user_model_id": "a"}, {"app_user_model_id": "b"}]}"#,
+        )
+        .unwrap();
+
+        let selector = "first".parse::<SessionSelector>().unwrap();
+        let selected = load(&list, Some(&selector)).unwrap();
+        let current = load(&list, None).unwrap();
+        let solo = load(&single, None).unwrap();
+        let unmatched = "third".parse::<SessionSelector>().unwrap();
+        let unmatched = load(&list, Some(&unmatched)).unwrap_err();
+        let ambiguous = load(&no_current, None).unwrap_err();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(selected.app_user_model_id, "org.mpris.MediaPlayer2.first");
+        assert_eq!(current.app_user_model_id, "second");
+        assert_eq!(solo.app_user_model_id, "solo");
+        assert!(unmatched.to_string().contains("no session in"));
+        assert!(ambiguous
+            .to_string()
+            .contains("holds 2 sessions and none is current"));
+    }
+}
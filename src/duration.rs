@@ -0,0 +1,129 @@
+//! Reading and writing durations the way people type them.
+
+use std::time::Duration;
+
+use anyhow::{anyhow, bail, Result};
+
+/// Parses a clock time (`2:31.5`, `1:02:03`), a sum of units (`1m30s`,
+/// `1.5h`, `500ms`) or plain seconds (`90`, `12.25`).
+pub fn parse(s: &str) -> Result<Duration> {
+    let s = s.trim();
+    if s.is_empty() {
+        bail!("empty duration");
+    }
+    let seconds = if s.contains(':') {
+        parse_clock(s)?
+    } else if s.ends_with(|c: char| c.is_ascii_alphabetic()) {
+        parse_units(s)?
+    } else {
+        number(s, s)?
+    };
+    Duration::try_from_secs_f64(seconds).map_err(|e| anyhow!("invalid duration {s:?}: {e}"))
+}
+
+/// `[[h:]m:]s[.fraction]`; only the seconds may have a fraction.
+fn parse_clock(s: &str) -> Result<f64> {
+    let parts = s.split(':').collect::<Vec<_>>();
+    if parts.len() > 3 {
+        bail!("invalid duration {s:?}: expected at most h:m:s");
+    }
+    let (seconds, whole) = parts.split_last().expect("split yields at least one part");
+    let mut total = 0.0;
+    for part in whole {
+        if part.contains('.') {
+            bail!("invalid duration {s:?}: only seconds may have a fraction");
+        }
+        total = total * 60.0 + number(part, s)?;
+    }
+    Ok(total * 60.0 + number(seconds, s)?)
+}
+
+/// A sequence of `<number><unit>` with units `h`, `m`, `s` and `ms`.
+fn parse_units(s: &str) -> Result<f64> {
+    let mut total = 0.0;
+    let mut rest = s;
+    while !rest.is_empty() {
+        let split = rest
+            .find(|c: char| c.is_ascii_alphabetic())
+            .ok_or_else(|| anyhow!("invalid duration {s:?}: {rest:?} has no unit"))?;
+        let (value, tail) = rest.split_at(split);
+        let unit_len = tail
+            .find(|c: char| !c.is_ascii_alphabetic())
+            .unwrap_or(tail.len());
+        let (unit, tail) = tail.split_at(unit_len);
+        let scale = match unit {
+            "h" => 3600.0,
+            "m" | "min" => 60.0,
+            "s" => 1.0,
+            "ms" => 0.001,
+            _ => bail!("invalid duration {s:?}: unknown unit {unit:?}"),
+        };
+        total += number(value, s)? * scale;
+        rest = tail;
+    }
+    Ok(total)
+}
+
+fn number(value: &str, whole: &str) -> Result<f64> {
+    if value.is_empty() || !value.chars().all(|c| c.is_ascii_digit() || c == '.') {
+        bail!("invalid duration {whole:?}: {value:?} is not a number");
+    }
+    value
+        .parse()
+        .map_err(|_| anyhow!("invalid duration {whole:?}: {value:?} is not a number"))
+}
+
+/// Formats as `mm:ss.mmm`, or `h:mm:ss.mmm` from an hour on.
+pub fn format(duration: Duration) -> String {
+    let millis = duration.as_millis();
+    let (hours, minutes) = (millis / 3_600_000, millis / 60_000 % 60);
+    let (seconds, millis) = (millis / 1000 % 60, millis % 1000);
+    if hours > 0 {
+        format!("{hours}:{minutes:02}:{seconds:02}.{millis:03}")
+    } else {
+        format!("{minutes:02}:{seconds:02}.{millis:03}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn secs(seconds: f64) -> Duration {
+        Duration::from_secs_f64(seconds)
+    }
+
+    #[test]
+    fn parses_clock_times() {
+        assert_eq!(parse("2:31.5").unwrap(), secs(151.5));
+        assert_eq!(parse("1:02:03").unwrap(), secs(3723.0));
+        assert_eq!(parse("0:07").unwrap(), secs(7.0));
+    }
+
+    #[test]
+    fn parses_units() {
+        assert_eq!(parse("1m30s").unwrap(), secs(90.0));
+        assert_eq!(parse("1.5h").unwrap(), secs(5400.0));
+        assert_eq!(parse("500ms").unwrap(), secs(0.5));
+        assert_eq!(parse("2min").unwrap(), secs(120.0));
+    }
+
+    #[test]
+    fn parses_plain_seconds() {
+        assert_eq!(parse("90").unwrap(), secs(90.0));
+        assert_eq!(parse(" 12.25 ").unwrap(), secs(12.25));
+    }
+
+    #[test]
+    fn rejects_malformed_durations() {
+        for input in ["", "1m30", "1:2:3:4", "1.5:00", "10x", "-5", "1..5s"] {
+            assert!(parse(input).is_err(), "{input:?} was accepted");
+        }
+    }
+
+    #[test]
+    fn formats_minutes_and_hours() {
+        assert_eq!(format(secs(151.5)), "02:31.500");
+        assert_eq!(format(secs(3723.0)), "1:02:03.000");
+    }
+}
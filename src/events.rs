@@ -0,0 +1,121 @@
+//! Timestamped, self-contained records of backend events.
+
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use crate::backend::{MediaBackend, MediaEvent};
+use crate::snapshot::{
+    nanos, FieldError, FieldErrors, MediaProperties, PlaybackInfo, SessionSnapshot,
+    TimelineProperties,
+};
+
+/// One line of an NDJSON event stream: the event together with the part of
+/// the session snapshot it affects, read right after the event arrived.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EventRecord {
+    /// Monotonic time since the stream started.
+    #[serde(with = "nanos")]
+    pub timestamp: Duration,
+    #[serde(flatten)]
+    pub event: MediaEvent,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub media_properties: Option<MediaProperties>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub playback_info: Option<PlaybackInfo>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeline_properties: Option<TimelineProperties>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<FieldError>,
+}
+
+impl EventRecord {
+    /// Stamps `event` relative to `started` and attaches the sections it
+    /// touched. Session-level events carry every section of the session.
+    pub async fn collect(backend: &dyn MediaBackend, event: MediaEvent, started: Instant) -> Self {
+        let mut record = Self {
+            timestamp: started.elapsed(),
+            event,
+            media_properties: None,
+            playback_info: None,
+            timeline_properties: None,
+            errors: vec![],
+        };
+
+        let session = match record.event.session() {
+            Some(id) => backend.session(id).await.ok().flatten(),
+            None => None,
+        };
+        let Some(session) = session else {
+            return record;
+        };
+
+        let (media, playback, timeline) = sections(&record.event);
+        if media {
+            let mut errors = FieldErrors::new("media_properties");
+            record.media_properties = MediaProperties::collect(session.as_ref(), &mut errors).await;
+            record.errors.extend(errors.into_vec());
+        }
+        if playback {
+            let mut errors = FieldErrors::new("playback_info");
+            record.playback_info = session.playback_info(&mut errors).await;
+            record.errors.extend(errors.into_vec());
+        }
+        if timeline {
+            let mut errors = FieldErrors::new("timeline_properties");
+            record.timeline_properties = session.timeline_properties(&mut errors).await;
+            record.errors.extend(errors.into_vec());
+        }
+        record
+    }
+
+    /// Writes the sections the record carries into `snapshot`, together with
+    /// their errors, as they were right after the event.
+    pub fn apply(&self, snapshot: &mut SessionSnapshot) {
+        let (media, playback, timeline) = sections(&self.event);
+        let mut replace_errors = |section: &str| {
+            snapshot.errors.retain(|error| !error.belongs_to(section));
+            snapshot.errors.extend(
+                self.errors
+                    .iter()
+                    .filter(|error| error.belongs_to(section))
+                    .cloned(),
+            );
+        };
+        if media {
+            replace_errors("media_properties");
+        }
+        if playback {
+            replace_errors("playback_info");
+        }
+        if timeline {
+            replace_errors("timeline_properties");
+        }
+
+        if media {
+            snapshot.media_properties = self.media_properties.clone();
+        }
+        if playback {
+            snapshot.playback_info = self.playback_info.clone();
+        }
+        if timeline {
+            snapshot.timeline_properties = self.timeline_properties.clone();
+        }
+    }
+}
+
+/// Which of media properties, playback info and timeline an event affects.
+/// Session-level events affect all of them.
+fn sections(event: &MediaEvent) -> (bool, bool, bool) {
+    match event {
+        MediaEvent::SessionAdded { .. } | MediaEvent::CurrentSessionChanged { .. } => {
+            (true, true, true)
+        }
+        MediaEvent::SessionRemoved { .. } => (false, false, false),
+        MediaEvent::MediaPropertiesChanged { .. } => (true, false, false),
+        MediaEvent::PlaybackInfoChanged { .. } => (false, true, false),
+        MediaEvent::TimelinePropertiesChanged { .. } | MediaEvent::Seeked { .. } => {
+            (false, false, true)
+        }
+    }
+}
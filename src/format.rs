@@ -0,0 +1,613 @@
+//! Renderers for [`SessionSnapshot`] and the values built from it.
+
+use std::io::{self, Write};
+use std::time::{Duration, SystemTime};
+
+use chrono::{DateTime, SecondsFormat, Utc};
+
+use serde::Serialize;
+
+use crate::backend::system_time;
+use crate::control::CommandOutcome;
+use crate::diff::SnapshotDiff;
+use crate::duration;
+use crate::snapshot::{
+    MediaProperties, PlaybackControls, PlaybackInfo, SessionList, SessionSnapshot,
+    TimelineProperties,
+};
+
+/// How the text output prints durations and timestamps.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TimeStyle {
+    /// Nanoseconds and 100ns FILETIME ticks, as the tool has always printed.
+    #[default]
+    Raw,
+    /// `mm:ss.mmm` durations and RFC 3339 UTC timestamps with their age.
+    Human,
+}
+
+/// A value that can be printed in every output format.
+pub trait Render: Serialize {
+    fn write_text(&self, w: &mut dyn Write, times: TimeStyle) -> io::Result<()>;
+}
+
+impl Render for SessionSnapshot {
+    fn write_text(&self, w: &mut dyn Write, times: TimeStyle) -> io::Result<()> {
+        write_text(w, self, times)
+    }
+}
+
+impl Render for SessionList {
+    fn write_text(&self, w: &mut dyn Write, times: TimeStyle) -> io::Result<()> {
+        let count = self.sessions.len();
+        for (index, session) in self.sessions.iter().enumerate() {
+            if index > 0 {
+                writeln!(w)?;
+            }
+            let marker = if session.is_current { " (current)" } else { "" };
+            writeln!(w, "# session {} of {count}{marker}", index + 1)?;
+            write_text(w, &session.snapshot, times)?;
+        }
+        Ok(())
+    }
+}
+
+impl Render for CommandOutcome {
+    fn write_text(&self, w: &mut dyn Write, _times: TimeStyle) -> io::Result<()> {
+        let verdict = if self.accepted {
+            "accepted"
+        } else {
+            "rejected"
+        };
+        write!(w, "{}", self.command)?;
+        if let Some(position) = self.position {
+            write!(w, " to {}", duration::format(position))?;
+        }
+        if let Some(value) = &self.value {
+            write!(w, " {value}")?;
+        }
+        write!(w, ": {verdict} by \"{}\"", self.session)?;
+        match (self.applied, &self.read_back) {
+            (Some(true), _) => writeln!(w, ", applied"),
+            (Some(false), Some(read_back)) => {
+                writeln!(w, ", not applied (player reports {read_back})")
+            }
+            (Some(false), None) => writeln!(w, ", not applied (player reports nothing)"),
+            (None, _) => writeln!(w),
+        }
+    }
+}
+
+impl Render for SnapshotDiff {
+    fn write_text(&self, w: &mut dyn Write, times: TimeStyle) -> io::Result<()> {
+        writeln!(w, "--- \"{}\"", self.left)?;
+        writeln!(w, "+++ \"{}\"", self.right)?;
+        if self.is_empty() {
+            return writeln!(w, "no differences");
+        }
+        for change in &self.changes {
+            writeln!(
+                w,
+                "{}: {} -> {}",
+                change.field,
+                diff_value(&change.field, &change.left, times),
+                diff_value(&change.field, &change.right, times)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Prints one side of a change as compact JSON, except for timeline values
+/// in [`TimeStyle::Human`], which print like in the snapshot listing.
+fn diff_value(field: &str, value: &serde_json::Value, times: TimeStyle) -> String {
+    if value.is_null() {
+        return "(none)".to_string();
+    }
+    let timeline = field.strip_prefix("timeline_properties.");
+    match (timeline, value.as_i64(), times) {
+        (Some("last_updated_time"), Some(ticks), TimeStyle::Human) => rfc3339(system_time(ticks)),
+        (Some(_), Some(nanos), TimeStyle::Human) => {
+            duration::format(Duration::from_nanos(nanos.unsigned_abs()))
+        }
+        _ => value.to_string(),
+    }
+}
+
+/// Writes the value as a single pretty-printed JSON document.
+pub fn write_json(w: &mut impl Write, value: &(impl Serialize + ?Sized)) -> io::Result<()> {
+    serde_json::to_writer_pretty(&mut *w, value)?;
+    writeln!(w)
+}
+
+/// Writes the value as compact JSON on a single line.
+pub fn write_ndjson(w: &mut impl Write, value: &(impl Serialize + ?Sized)) -> io::Result<()> {
+    serde_json::to_writer(&mut *w, value)?;
+    writeln!(w)
+}
+
+/// Writes the value as a YAML document.
+pub fn write_yaml(w: &mut impl Write, value: &(impl Serialize + ?Sized)) -> io::Result<()> {
+    serde_yaml::to_writer(&mut *w, value).map_err(io::Error::other)
+}
+
+/// Writes the value as a TOML document. TOML has no null, so fields the
+/// platform did not provide are omitted rather than written out.
+pub fn write_toml(w: &mut impl Write, value: &(impl Serialize + ?Sized)) -> io::Result<()> {
+    let document = toml::to_string_pretty(value).map_err(io::Error::other)?;
+    w.write_all(document.as_bytes())
+}
+
+/// Writes the indented listing the tool has always printed.
+pub fn write_text(
+    w: &mut dyn Write,
+    snapshot: &SessionSnapshot,
+    times: TimeStyle,
+) -> io::Result<()> {
+    writeln!(w, "app_user_model_id: \"{}\"", snapshot.app_user_model_id)?;
+
+    writeln!(w)?;
+
+    writeln!(w, "media_properties:")?;
+    if let Some(media_properties) = &snapshot.media_properties {
+        write_media_properties(w, media_properties, 1)?;
+    }
+
+    writeln!(w, "    playback_info:")?;
+    if let Some(playback_info) = &snapshot.playback_info {
+        write_playback_info(w, playback_info, 2)?;
+    }
+    writeln!(w)?;
+
+    writeln!(w, "    timeline_properties:")?;
+    if let Some(timeline_properties) = &snapshot.timeline_properties {
+        write_timeline_properties(w, timeline_properties, 2, times)?;
+    }
+
+    if !snapshot.errors.is_empty() {
+        writeln!(w)?;
+        writeln!(w, "errors:")?;
+        for error in &snapshot.errors {
+            match &error.code {
+                Some(code) => writeln!(w, "    - {} ({code}): {}", error.field, error.message)?,
+                None => writeln!(w, "    - {}: {}", error.field, error.message)?,
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn write_timeline_properties(
+    w: &mut dyn Write,
+    timeline_properties: &TimelineProperties,
+    depth: usize,
+    times: TimeStyle,
+) -> io::Result<()> {
+    let prefix = " ".chars().cycle().take(depth * 4).collect::<String>();
+    let TimelineProperties {
+        start_time,
+        end_time,
+        max_seek_time,
+        min_seek_time,
+        position,
+        last_updated_time,
+    } = timeline_properties;
+
+    for (name, value) in [
+        ("start_time", start_time),
+        ("end_time", end_time),
+        ("max_seek_time", max_seek_time),
+        ("min_seek_time", min_seek_time),
+        ("position", position),
+    ] {
+        match (value, times) {
+            (Some(value), TimeStyle::Raw) => {
+                writeln!(w, "{prefix}{name}: {}", value.as_nanos())?;
+            }
+            (Some(value), TimeStyle::Human) => {
+                writeln!(w, "{prefix}{name}: {}", duration::format(*value))?;
+            }
+            (None, _) => {}
+        }
+    }
+    match (last_updated_time, times) {
+        (Some(last_updated_time), TimeStyle::Raw) => {
+            writeln!(w, "{prefix}last_updated_time: {last_updated_time}")?;
+        }
+        (Some(last_updated_time), TimeStyle::Human) => {
+            let updated = system_time(*last_updated_time);
+            let age = match SystemTime::now().duration_since(updated) {
+                Ok(age) => format!("{} ago", duration::format(age)),
+                Err(_) => "in the future".to_string(),
+            };
+            writeln!(w, "{prefix}last_updated_time: {} ({age})", rfc3339(updated))?;
+        }
+        (None, _) => {}
+    }
+    Ok(())
+}
+
+/// Formats as RFC 3339 UTC with milliseconds, e.g. `2024-05-01T12:00:00.000Z`.
+fn rfc3339(time: SystemTime) -> String {
+    DateTime::<Utc>::from(time).to_rfc3339_opts(SecondsFormat::Millis, true)
+}
+
+fn write_playback_info(
+    w: &mut dyn Write,
+    playback_info: &PlaybackInfo,
+    depth: usize,
+) -> io::Result<()> {
+    let prefix = " ".chars().cycle().take(depth * 4).collect::<String>();
+
+    if let Some(auto_repeat_mode) = playback_info.auto_repeat_mode {
+        writeln!(w, "{prefix}auto_repeat_mode: \"{auto_repeat_mode}\"")?;
+    }
+
+    if let Some(controls) = &playback_info.controls {
+        writeln!(w, "{prefix}controls:")?;
+        write_playback_controls(w, controls, depth + 1)?;
+    }
+
+    if let Some(is_shuffle_active) = playback_info.is_shuffle_active {
+        writeln!(w, "{prefix}is_shuffle_active: {is_shuffle_active}")?;
+    }
+
+    if let Some(playback_rate) = playback_info.playback_rate {
+        writeln!(w, "{prefix}playback_rate: {playback_rate:.02}")?;
+    }
+
+    if let Some(playback_status) = playback_info.playback_status {
+        writeln!(w, "{prefix}playback_status: \"{playback_status}\"")?;
+    }
+
+    if let Some(playback_type) = playback_info.playback_type {
+        writeln!(w, "{prefix}playback_type: \"{playback_type}\"")?;
+    }
+
+    Ok(())
+}
+
+fn write_playback_controls(
+    w: &mut dyn Write,
+    controls: &PlaybackControls,
+    depth: usize,
+) -> io::Result<()> {
+    let prefix = " ".chars().cycle().take(depth * 4).collect::<String>();
+
+    let PlaybackControls {
+        is_channel_down_enabled,
+        is_channel_up_enabled,
+        is_fast_forward_enabled,
+        is_next_enabled,
+        is_pause_enabled,
+        is_playback_position_enabled,
+        is_playback_rate_enabled,
+        is_play_enabled,
+        is_play_pause_toggle_enabled,
+        is_previous_enabled,
+        is_record_enabled,
+        is_repeat_enabled,
+        is_rewind_enabled,
+        is_shuffle_enabled,
+        is_stop_enabled,
+    } = controls;
+
+    for (name, value) in [
+        ("is_channel_down_enabled", is_channel_down_enabled),
+        ("is_channel_up_enabled", is_channel_up_enabled),
+        ("is_fast_forward_enabled", is_fast_forward_enabled),
+        ("is_next_enabled", is_next_enabled),
+        ("is_pause_enabled", is_pause_enabled),
+        ("is_playback_position_enabled", is_playback_position_enabled),
+        ("is_playback_rate_enabled", is_playback_rate_enabled),
+        ("is_play_enabled", is_play_enabled),
+        ("is_play_pause_toggle_enabled", is_play_pause_toggle_enabled),
+        ("is_previous_enabled", is_previous_enabled),
+        ("is_record_enabled", is_record_enabled),
+        ("is_repeat_enabled", is_repeat_enabled),
+        ("is_rewind_enabled", is_rewind_enabled),
+        ("is_shuffle_enabled", is_shuffle_enabled),
+        ("is_stop_enabled", is_stop_enabled),
+    ] {
+        if let Some(value) = value {
+            writeln!(w, "{prefix}{name}: {value}")?;
+        }
+    }
+    Ok(())
+}
+
+fn write_media_properties(
+    w: &mut dyn Write,
+    media_properties: &MediaProperties,
+    depth: usize,
+) -> io::Result<()> {
+    let prefix = " ".chars().cycle().take(depth * 4).collect::<String>();
+    let MediaProperties {
+        album_artist,
+        album_title,
+        album_track_count,
+        artist,
+        genres,
+        playback_type,
+        subtitle,
+        thumbnail,
+        title,
+        track_number,
+    } = media_properties;
+
+    if let Some(album_artist) = album_artist {
+        writeln!(w, "{prefix}album_artist: \"{album_artist}\"")?;
+    }
+    if let Some(album_title) = album_title {
+        writeln!(w, "{prefix}album_title: \"{album_title}\"")?;
+    }
+    if let Some(album_track_count) = album_track_count {
+        writeln!(w, "{prefix}album_track_count: {album_track_count}")?;
+    }
+    if let Some(artist) = artist {
+        writeln!(w, "{prefix}artist: \"{artist}\"")?;
+    }
+    writeln!(w, "{prefix}genres:")?;
+    for genre in genres {
+        writeln!(w, "{prefix}     - \"{genre}\"")?;
+    }
+    if let Some(playback_type) = playback_type {
+        writeln!(w, "{prefix}playback_type: {playback_type}")?;
+    }
+    if let Some(subtitle) = subtitle {
+        writeln!(w, "{prefix}subtitle: {subtitle}")?;
+    }
+    if let Some(thumbnail) = thumbnail {
+        writeln!(w, "{prefix}thumbnail:")?;
+        if let Some(content_type) = &thumbnail.content_type {
+            writeln!(w, "{prefix}    content_type: {content_type}")?;
+        }
+        if let Some(size) = thumbnail.size {
+            writeln!(w, "{prefix}    size: {size}")?;
+        }
+        if let Some(url) = &thumbnail.url {
+            writeln!(w, "{prefix}    url: \"{url}\"")?;
+        }
+        if let Some(detected_content_type) = &thumbnail.detected_content_type {
+            writeln!(
+                w,
+                "{prefix}    detected_content_type: {detected_content_type}"
+            )?;
+        }
+        if let Some(width) = thumbnail.width {
+            writeln!(w, "{prefix}    width: {width}")?;
+        }
+        if let Some(height) = thumbnail.height {
+            writeln!(w, "{prefix}    height: {height}")?;
+        }
+        if let Some(sha256) = &thumbnail.sha256 {
+            writeln!(w, "{prefix}    sha256: {sha256}")?;
+        }
+    }
+    if let Some(title) = title {
+        writeln!(w, "{prefix}title: {title}")?;
+    }
+    if let Some(track_number) = track_number {
+        writeln!(w, "{prefix}track_number: {track_number}")?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::mock::MockBackend;
+    use crate::backend::MediaBackend;
+
+    const STATE: &str = r#"{
+        "sessions": [
+            {
+                "is_current": true,
+                "app_user_model_id": "player",
+                "media_properties": { "title": "Song", "artist": "Band", "genres": ["Rock"] },
+                "playback_info": {
+                    "auto_repeat_mode": "List",
+                    "playback_rate": 1.0,
+                    "playback_status": "Paused"
+                },
+                "timeline_properties": {
+                    "start_time": 0,
+                    "end_time": 180000000000,
+                    "position": 42500000000
+                },
+                "errors": [
+                    { "field": "media_properties.subtitle", "code": "0x80004005", "message": "Unspecified error" }
+                ]
+            }
+        ]
+    }"#;
+
+    async fn collect() -> SessionSnapshot {
+        let backend = MockBackend::new(serde_json::from_str(STATE).unwrap());
+        let session = backend.current_session().await.unwrap().unwrap();
+        SessionSnapshot::collect(session.as_ref()).await
+    }
+
+    fn render(write: impl FnOnce(&mut Vec<u8>) -> io::Result<()>) -> String {
+        let mut output = vec![];
+        write(&mut output).unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[tokio::test]
+    async fn renders_raw_text() {
+        let snapshot = collect().await;
+        assert_eq!(
+            render(|w| snapshot.write_text(w, TimeStyle::Raw)),
+            r#"app_user_model_id: "player"
+
+media_properties:
+    artist: "Band"
+    genres:
+         - "Rock"
+    title: Song
+    playback_info:
+        auto_repeat_mode: "List"
+        playback_rate: 1.00
+        playback_status: "Paused"
+
+    timeline_properties:
+        start_time: 0
+        end_time: 180000000000
+        position: 42500000000
+
+errors:
+    - media_properties.subtitle (0x80004005): Unspecified error
+"#
+        );
+    }
+
+    #[tokio::test]
+    async fn renders_human_text() {
+        let snapshot = collect().await;
+        assert_eq!(
+            render(|w| snapshot.write_text(w, TimeStyle::Human)),
+            r#"app_user_model_id: "player"
+
+media_properties:
+    artist: "Band"
+    genres:
+         - "Rock"
+    title: Song
+    playback_info:
+        auto_repeat_mode: "List"
+        playback_rate: 1.00
+        playback_status: "Paused"
+
+    timeline_properties:
+        start_time: 00:00.000
+        end_time: 03:00.000
+        position: 00:42.500
+
+errors:
+    - media_properties.subtitle (0x80004005): Unspecified error
+"#
+        );
+    }
+
+    #[tokio::test]
+    async fn renders_json() {
+        let snapshot = collect().await;
+        assert_eq!(
+            render(|w| write_json(w, &snapshot)),
+            r#"{
+  "app_user_model_id": "player",
+  "media_properties": {
+    "album_artist": null,
+    "album_title": null,
+    "album_track_count": null,
+    "artist": "Band",
+    "genres": [
+      "Rock"
+    ],
+    "playback_type": null,
+    "subtitle": null,
+    "thumbnail": null,
+    "title": "Song",
+    "track_number": null
+  },
+  "playback_info": {
+    "auto_repeat_mode": "List",
+    "controls": null,
+    "is_shuffle_active": null,
+    "playback_rate": 1.0,
+    "playback_status": "Paused",
+    "playback_type": null
+  },
+  "timeline_properties": {
+    "start_time": 0,
+    "end_time": 180000000000,
+    "max_seek_time": null,
+    "min_seek_time": null,
+    "position": 42500000000,
+    "last_updated_time": null
+  },
+  "errors": [
+    {
+      "field": "media_properties.subtitle",
+      "code": "0x80004005",
+      "message": "Unspecified error"
+    }
+  ]
+}
+"#
+        );
+    }
+
+    #[tokio::test]
+    async fn renders_yaml() {
+        let snapshot = collect().await;
+        assert_eq!(
+            render(|w| write_yaml(w, &snapshot)),
+            r#"app_user_model_id: player
+media_properties:
+  album_artist: null
+  album_title: null
+  album_track_count: null
+  artist: Band
+  genres:
+  - Rock
+  playback_type: null
+  subtitle: null
+  thumbnail: null
+  title: Song
+  track_number: null
+playback_info:
+  auto_repeat_mode: List
+  controls: null
+  is_shuffle_active: null
+  playback_rate: 1.0
+  playback_status: Paused
+  playback_type: null
+timeline_properties:
+  start_time: 0
+  end_time: 180000000000
+  max_seek_time: null
+  min_seek_time: null
+  position: 42500000000
+  last_updated_time: null
+errors:
+- field: media_properties.subtitle
+  code: '0x80004005'
+  message: Unspecified error
+"#
+        );
+    }
+
+    #[tokio::test]
+    async fn renders_toml_without_nulls() {
+        let snapshot = collect().await;
+        assert_eq!(
+            render(|w| write_toml(w, &snapshot)),
+            r#"app_user_model_id = "player"
+
+[media_properties]
+artist = "Band"
+genres = ["Rock"]
+title = "Song"
+
+[playback_info]
+auto_repeat_mode = "List"
+playback_rate = 1.0
+playback_status = "Paused"
+
+[timeline_properties]
+start_time = 0
+end_time = 180000000000
+position = 42500000000
+
+[[errors]]
+field = "media_properties.subtitle"
+code = "0x80004005"
+message = "Unspecified error"
+"#
+        );
+    }
+}
@@ -0,0 +1,18 @@
+pub mod backend;
+#[cfg(target_os = "linux")]
+pub mod bridge;
+pub mod control;
+pub mod diff;
+pub mod duration;
+pub mod events;
+pub mod format;
+pub mod patch;
+pub mod position;
+pub mod preview;
+pub mod selector;
+pub mod server;
+pub mod snapshot;
+#[cfg(all(test, target_os = "linux"))]
+mod testbus;
+pub mod thumbnail;
+pub mod trace;
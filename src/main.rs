@@ -1,191 +1,416 @@
-use std::time::Duration;
+use std::fs::File;
+use std::io::{self, Write};
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::process;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
-use anyhow::Result;
-use windows::Foundation::DateTime;
-use windows::Media::Control::GlobalSystemMediaTransportControlsSession as GSMTCSession;
-use windows::Media::Control::GlobalSystemMediaTransportControlsSessionManager as GSMTCSessionManager;
-use windows::Media::Control::GlobalSystemMediaTransportControlsSessionPlaybackControls as GSMTCPlaybackControls;
+use anyhow::{anyhow, Context, Result};
+use clap::error::ErrorKind;
+use clap::{CommandFactory, Parser, Subcommand, ValueEnum};
+use test_gsmtc::backend::mock::MockBackend;
+use test_gsmtc::backend::remote::RemoteBackend;
+use test_gsmtc::backend::replay::ReplayBackend;
+use test_gsmtc::backend::{self, MediaBackend, MediaEvent, MediaSession};
+#[cfg(target_os = "linux")]
+use test_gsmtc::bridge;
+use test_gsmtc::control::{self, PlaybackCommand, SeekOffset};
+use test_gsmtc::diff::{self, SnapshotDiff};
+use test_gsmtc::duration;
+use test_gsmtc::events::EventRecord;
+use test_gsmtc::format::{self, Render, TimeStyle};
+use test_gsmtc::preview::{self, Protocol};
+use test_gsmtc::selector::SessionSelector;
+use test_gsmtc::server;
+use test_gsmtc::snapshot::{RepeatMode, SessionList, SessionSnapshot};
+use test_gsmtc::thumbnail::ThumbnailData;
+use test_gsmtc::trace;
 
-#[tokio::main]
-async fn main() -> Result<()> {
-    let session_manager = GSMTCSessionManager::RequestAsync()?.await?;
-    let current_session = session_manager.GetCurrentSession()?;
+#[derive(Parser)]
+#[command(about = "Dumps what the system media session reports")]
+struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
 
-    let app_user_model_id = current_session.SourceAppUserModelId()?;
-    println!("app_user_model_id: \"{app_user_model_id}\"");
+    /// Output format
+    #[arg(long, value_enum, default_value_t = Format::Text, global = true)]
+    format: Format,
 
-    println!();
+    /// Dump every session instead of only the current one; not with a
+    /// subcommand
+    #[arg(long, conflicts_with = "session")]
+    all: bool,
 
-    println!("media_properties:");
-    let _ = print_media_properties(&current_session, 1).await;
+    /// Also save the session's cover art to this file or directory; the
+    /// extension is added when missing. Not with a subcommand
+    #[arg(long, value_name = "PATH", conflicts_with = "all")]
+    save_thumbnail: Option<PathBuf>,
 
-    println!("    playback_info:");
-    let _ = print_playback_info(&current_session, 2);
-    println!();
+    /// Read sessions from a state file instead of the system: JSON, YAML or
+    /// TOML as printed by `--all` or for a single session
+    #[arg(long, value_name = "FILE", global = true)]
+    mock: Option<PathBuf>,
 
-    println!("    timeline_properties:");
-    let _ = print_timeline_properties(&current_session, 2);
+    /// Play a trace written by `record` back instead of reading the system.
+    /// Playback starts once something watches the sessions
+    #[arg(long, value_name = "FILE", global = true, conflicts_with = "mock")]
+    replay: Option<PathBuf>,
 
-    Ok(())
+    /// How many times faster than recorded to replay, e.g. `10`
+    #[arg(long, default_value_t = 1.0, global = true, requires = "replay")]
+    speed: f64,
+
+    /// Mirror the current session of another machine running `serve`
+    /// instead of reading the system, e.g. `http://192.168.1.2:7878`
+    #[arg(
+        long,
+        value_name = "URL",
+        global = true,
+        conflicts_with_all = ["mock", "replay"]
+    )]
+    remote: Option<String>,
+
+    /// Use the session whose id matches: an exact id, a glob, or `re:<regex>`
+    #[arg(long, short, global = true)]
+    session: Option<SessionSelector>,
 }
 
-fn print_timeline_properties(session: &GSMTCSession, depth: usize) -> Result<()> {
-    let prefix = " ".chars().cycle().take(depth * 4).collect::<String>();
-    let timeline_properties = session.GetTimelineProperties()?;
-
-    let start_time: Duration = timeline_properties.StartTime()?.into();
-    let end_time: Duration = timeline_properties.EndTime()?.into();
-    let max_seek_time: Duration = timeline_properties.MaxSeekTime()?.into();
-    let min_seek_time: Duration = timeline_properties.MinSeekTime()?.into();
-    let position: Duration = timeline_properties.Position()?.into();
-    let last_updated_time: DateTime = timeline_properties.LastUpdatedTime()?.into();
-
-    println!("{prefix}start_time: {}", start_time.as_nanos());
-    println!("{prefix}end_time: {}", end_time.as_nanos());
-    println!("{prefix}max_seek_time: {}", max_seek_time.as_nanos());
-    println!("{prefix}min_seek_time: {}", min_seek_time.as_nanos());
-    println!("{prefix}position: {}", position.as_nanos());
-    println!(
-        "{prefix}last_updated_time: {}",
-        last_updated_time.UniversalTime
-    );
-    Ok(())
+#[derive(Subcommand)]
+enum Command {
+    /// Print every change the backend reports, followed by the refreshed
+    /// snapshot of the watched session, until interrupted. With
+    /// `--format ndjson`, print one timestamped JSON record per event instead
+    Watch {
+        /// Only print the event lines
+        #[arg(long)]
+        events_only: bool,
+    },
+    /// Start or resume playback
+    Play,
+    /// Pause playback
+    Pause,
+    /// Toggle between playing and paused
+    Toggle,
+    /// Skip to the next track
+    Next,
+    /// Skip to the previous track
+    Previous,
+    /// Stop playback
+    Stop,
+    /// Move playback forward or back, e.g. `+10s`, `-1m30s`
+    #[command(allow_negative_numbers = true)]
+    Seek {
+        #[arg(allow_hyphen_values = true)]
+        offset: SeekOffset,
+    },
+    /// Move playback to a position from the start, e.g. `2:31.5`, `90s`
+    Position {
+        #[arg(value_parser = duration::parse)]
+        position: Duration,
+    },
+    /// Set the repeat mode: none, track or list
+    Repeat { mode: RepeatMode },
+    /// Turn shuffle on or off; flips it when no state is given
+    Shuffle {
+        #[arg(value_parser = control::parse_switch)]
+        state: Option<bool>,
+    },
+    /// Set the playback rate, e.g. `1.5`
+    Rate { rate: f64 },
+    /// Draw the session's cover art in the terminal
+    Cover {
+        /// How to draw it: auto, blocks, kitty or sixel
+        #[arg(long, default_value_t = Protocol::Auto)]
+        protocol: Protocol,
+        /// Width in terminal columns
+        #[arg(long, default_value_t = 40)]
+        width: u32,
+    },
+    /// Publish the session on the session bus as an MPRIS2 player, forwarding
+    /// calls to it, until interrupted. Follows the current session unless
+    /// `--session` is given; with `--remote`, that of another machine
+    Bridge {
+        /// Bus name suffix, after `org.mpris.MediaPlayer2.`
+        #[arg(long, default_value = "testgsmtc")]
+        name: String,
+    },
+    /// Compare two snapshot files, or a snapshot file with the live session,
+    /// field by field. Files hold a session as dumped, or a list as printed
+    /// by `--all`, of which the current one is compared unless `--session`
+    /// picks another. Exits with 1 when they differ
+    Diff {
+        /// Snapshot file on the left
+        left: PathBuf,
+        /// Snapshot file on the right; the live session when left out
+        right: Option<PathBuf>,
+    },
+    /// Record every session and then each event with timestamps to a trace
+    /// file until interrupted, for `--replay`
+    Record {
+        /// Trace file to write; `-` writes to stdout
+        path: PathBuf,
+    },
+    /// Serve the sessions over a local HTTP JSON API until interrupted.
+    /// `--session` picks what `/sessions/current` refers to
+    Serve {
+        /// Address to listen on
+        #[arg(long, default_value = "127.0.0.1:7878")]
+        listen: SocketAddr,
+        /// How often WebSocket feeds send the extrapolated position, e.g.
+        /// `100ms`; `0` turns it off. Clients can override it with `?tick=`
+        #[arg(long, default_value = "250ms", value_parser = duration::parse)]
+        tick: Duration,
+    },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Format {
+    Text,
+    /// Text with readable durations and timestamps
+    Human,
+    Json,
+    /// Compact JSON, one document per line
+    Ndjson,
+    Yaml,
+    Toml,
 }
 
-fn print_playback_info(session: &GSMTCSession, depth: usize) -> Result<()> {
-    let prefix = " ".chars().cycle().take(depth * 4).collect::<String>();
-    let playback_info = session.GetPlaybackInfo()?;
-
-    if let Ok(auto_repeat_mode) = playback_info.AutoRepeatMode().and_then(|v| v.Value()) {
-        println!(
-            "{prefix}auto_repeat_mode: \"{}\"",
-            match auto_repeat_mode.0 {
-                0 => "None",
-                1 => "Track",
-                2 => "List",
-                _ => unreachable!(),
-            }
+#[tokio::main]
+async fn main() -> Result<()> {
+    let args = Args::parse();
+    let dump_only = [
+        ("--all", args.all),
+        ("--save-thumbnail", args.save_thumbnail.is_some()),
+    ];
+    if let Some((flag, _)) = dump_only.iter().find(|(_, given)| *given) {
+        if args.command.is_some() {
+            Args::command()
+                .error(
+                    ErrorKind::ArgumentConflict,
+                    format!("{flag} only applies to the dump and cannot be used with a subcommand"),
+                )
+                .exit();
+        }
+    }
+
+    if let Some(Command::Diff {
+        left,
+        right: Some(right),
+    }) = &args.command
+    {
+        let left = diff::load(left, args.session.as_ref())?;
+        let right = diff::load(right, args.session.as_ref())?;
+        return show_diff(args.format, &left, &right);
+    }
+
+    let backend: Box<dyn MediaBackend> = match (&args.mock, &args.replay, &args.remote) {
+        (Some(path), _, _) => Box::new(MockBackend::load(path)?),
+        (None, Some(path), _) => Box::new(ReplayBackend::load(path, args.speed)?),
+        (None, None, Some(url)) => Box::new(RemoteBackend::connect(url).await?),
+        (None, None, None) => backend::default_backend().await?,
+    };
+
+    if let Some(Command::Watch { events_only }) = args.command {
+        return watch(backend.as_ref(), &args, events_only).await;
+    }
+
+    if let Some(Command::Bridge { name }) = &args.command {
+        #[cfg(target_os = "linux")]
+        return bridge::run(Arc::from(backend), args.session, name).await;
+        #[cfg(not(target_os = "linux"))]
+        anyhow::bail!(
+            "cannot publish {name:?}: bridge mode needs D-Bus, which is only used on Linux"
         );
     }
 
-    if let Ok(controls) = playback_info.Controls() {
-        println!("{prefix}controls:");
-        print_playback_controls(controls, depth + 1)?;
+    if let Some(Command::Record { path }) = &args.command {
+        if path.as_os_str() == "-" {
+            return trace::record(backend.as_ref(), &mut io::stdout()).await;
+        }
+        let mut file =
+            File::create(path).with_context(|| format!("cannot create {}", path.display()))?;
+        eprintln!("recording to {}", path.display());
+        return trace::record(backend.as_ref(), &mut file).await;
     }
 
-    if let Ok(is_shuffle_active) = playback_info.IsShuffleActive().and_then(|v| v.Value()) {
-        println!("{prefix}is_shuffle_active: {is_shuffle_active}");
+    if let Some(Command::Serve { listen, tick }) = args.command {
+        return server::serve(Arc::from(backend), args.session, tick, listen).await;
     }
 
-    if let Ok(playback_rate) = playback_info.PlaybackRate().and_then(|info| info.Value()) {
-        println!("{prefix}playback_rate: {playback_rate:.02}");
+    if args.all {
+        let sessions = SessionList::collect(backend.as_ref()).await?;
+        return render(args.format, &sessions);
     }
 
-    if let Ok(playback_status) = playback_info.PlaybackStatus() {
-        println!(
-            "{prefix}playback_status: \"{}\"",
-            match playback_status.0 {
-                0 => "Closed",
-                1 => "Opened",
-                2 => "Changing",
-                3 => "Stopped",
-                4 => "Playing",
-                5 => "Paused",
-                _ => unreachable!(),
-            }
-        );
+    let session = find_session(backend.as_ref(), args.session.as_ref())
+        .await?
+        .ok_or_else(|| anyhow!("no media session is active"))?;
+
+    let session = session.as_ref();
+    if let Some(Command::Diff { left, right: None }) = &args.command {
+        let left = diff::load(left, args.session.as_ref())?;
+        let right = SessionSnapshot::collect(session).await;
+        return show_diff(args.format, &left, &right);
     }
 
-    if let Ok(playback_type) = playback_info.PlaybackType().and_then(|v| v.Value()) {
-        println!(
-            "{prefix}playback_type: \"{}\"",
-            playback_type_str(playback_type.0)
+    let outcome = match args.command {
+        Some(
+            Command::Watch { .. }
+            | Command::Cover { .. }
+            | Command::Diff { .. }
+            | Command::Bridge { .. }
+            | Command::Record { .. }
+            | Command::Serve { .. },
+        )
+        | None => None,
+        Some(Command::Play) => Some(control::send(session, PlaybackCommand::Play).await?),
+        Some(Command::Pause) => Some(control::send(session, PlaybackCommand::Pause).await?),
+        Some(Command::Toggle) => {
+            Some(control::send(session, PlaybackCommand::TogglePlayPause).await?)
+        }
+        Some(Command::Next) => Some(control::send(session, PlaybackCommand::Next).await?),
+        Some(Command::Previous) => Some(control::send(session, PlaybackCommand::Previous).await?),
+        Some(Command::Stop) => Some(control::send(session, PlaybackCommand::Stop).await?),
+        Some(Command::Seek { offset }) => Some(control::seek(session, offset).await?),
+        Some(Command::Position { position }) => {
+            Some(control::set_position(session, position).await?)
+        }
+        Some(Command::Repeat { mode }) => Some(control::set_repeat_mode(session, mode).await?),
+        Some(Command::Shuffle { state }) => Some(control::set_shuffle(session, state).await?),
+        Some(Command::Rate { rate }) => Some(control::set_playback_rate(session, rate).await?),
+    };
+    if let Some(outcome) = outcome {
+        render(args.format, &outcome)?;
+        if !outcome.succeeded() {
+            process::exit(1);
+        }
+        return Ok(());
+    }
+
+    if let Some(Command::Cover { protocol, width }) = args.command {
+        let thumbnail = require_thumbnail(session).await?;
+        let mut stdout = io::stdout().lock();
+        preview::draw(&mut stdout, &thumbnail, protocol, width)?;
+        stdout.flush()?;
+        return Ok(());
+    }
+
+    if let Some(path) = &args.save_thumbnail {
+        let thumbnail = require_thumbnail(session).await?;
+        let path = thumbnail.save(path)?;
+        eprintln!(
+            "saved {} bytes of {} to {}",
+            thumbnail.bytes.len(),
+            thumbnail
+                .content_type
+                .as_deref()
+                .unwrap_or("unknown content"),
+            path.display()
         );
     }
 
-    Ok(())
+    let snapshot = SessionSnapshot::collect(session).await;
+    render(args.format, &snapshot)
 }
 
-fn print_playback_controls(controls: GSMTCPlaybackControls, depth: usize) -> Result<()> {
-    let prefix = " ".chars().cycle().take(depth * 4).collect::<String>();
-
-    let is_channel_down_enabled = controls.IsChannelDownEnabled()?;
-    let is_channel_up_enabled = controls.IsChannelUpEnabled()?;
-    let is_fast_forward_enabled = controls.IsFastForwardEnabled()?;
-    let is_next_enabled = controls.IsNextEnabled()?;
-    let is_pause_enabled = controls.IsPauseEnabled()?;
-    let is_playback_position_enabled = controls.IsPlaybackPositionEnabled()?;
-    let is_playback_rate_enabled = controls.IsPlaybackRateEnabled()?;
-    let is_play_enabled = controls.IsPlayEnabled()?;
-    let is_play_pause_toggle_enabled = controls.IsPlayPauseToggleEnabled()?;
-    let is_previous_enabled = controls.IsPreviousEnabled()?;
-    let is_record_enabled = controls.IsRecordEnabled()?;
-    let is_repeat_enabled = controls.IsRepeatEnabled()?;
-    let is_rewind_enabled = controls.IsRewindEnabled()?;
-    let is_shuffle_enabled = controls.IsShuffleEnabled()?;
-    let is_stop_enabled = controls.IsStopEnabled()?;
-
-    println!("{prefix}is_channel_down_enabled: {is_channel_down_enabled}");
-    println!("{prefix}is_channel_up_enabled: {is_channel_up_enabled}");
-    println!("{prefix}is_fast_forward_enabled: {is_fast_forward_enabled}");
-    println!("{prefix}is_next_enabled: {is_next_enabled}");
-    println!("{prefix}is_pause_enabled: {is_pause_enabled}");
-    println!("{prefix}is_playback_position_enabled: {is_playback_position_enabled}");
-    println!("{prefix}is_playback_rate_enabled: {is_playback_rate_enabled}");
-    println!("{prefix}is_play_enabled: {is_play_enabled}");
-    println!("{prefix}is_play_pause_toggle_enabled: {is_play_pause_toggle_enabled}");
-    println!("{prefix}is_previous_enabled: {is_previous_enabled}");
-    println!("{prefix}is_record_enabled: {is_record_enabled}");
-    println!("{prefix}is_repeat_enabled: {is_repeat_enabled}");
-    println!("{prefix}is_rewind_enabled: {is_rewind_enabled}");
-    println!("{prefix}is_shuffle_enabled: {is_shuffle_enabled}");
-    println!("{prefix}is_stop_enabled: {is_stop_enabled}");
+/// Prints the differences, exiting with 1 when there are any.
+fn show_diff(format: Format, left: &SessionSnapshot, right: &SessionSnapshot) -> Result<()> {
+    let diff = SnapshotDiff::between(left, right);
+    render(format, &diff)?;
+    if !diff.is_empty() {
+        process::exit(1);
+    }
     Ok(())
 }
 
-async fn print_media_properties(session: &GSMTCSession, depth: usize) -> Result<()> {
-    let prefix = " ".chars().cycle().take(depth * 4).collect::<String>();
-    let media_properties = session.TryGetMediaPropertiesAsync()?.await?;
-
-    let album_artist = media_properties.AlbumArtist()?;
-    let album_title = media_properties.AlbumTitle()?;
-    let album_track_count = media_properties.AlbumTrackCount()?;
-    let artist = media_properties.Artist()?;
-    let genres = media_properties.Genres()?;
-    let playback_type = media_properties.PlaybackType()?.Value()?.0;
-    let subtitle = media_properties.Subtitle()?;
-    let thumbnail = media_properties.Thumbnail()?.OpenReadAsync()?.await?;
-    let title = media_properties.Title()?;
-    let track_number = media_properties.TrackNumber()?;
-
-    println!("{prefix}album_artist: \"{album_artist}\"");
-    println!("{prefix}album_title: \"{album_title}\"");
-    println!("{prefix}album_track_count: {album_track_count}");
-    println!("{prefix}artist: \"{artist}\"");
-    println!("{prefix}genres:");
-    for genre in genres {
-        println!("{prefix}     - \"{genre}\"");
-    }
-    println!(
-        "{prefix}playback_type: {}",
-        playback_type_str(playback_type)
-    );
-    println!("{prefix}subtitle: {subtitle}");
-    println!("{prefix}thumbnail:");
-    println!("{prefix}    content_type: {}", thumbnail.ContentType()?);
-    println!("{prefix}    size: {}", thumbnail.Size()?);
-    println!("{prefix}title: {title}");
-    println!("{prefix}track_number: {track_number}");
+async fn require_thumbnail(session: &dyn MediaSession) -> Result<ThumbnailData> {
+    session
+        .thumbnail()
+        .await?
+        .ok_or_else(|| anyhow!("{} has no thumbnail", session.id()))
+}
+
+/// Resolves `--session`, falling back to the backend's current session.
+async fn find_session(
+    backend: &dyn MediaBackend,
+    selector: Option<&SessionSelector>,
+) -> Result<Option<Box<dyn MediaSession>>> {
+    match selector {
+        Some(selector) => Ok(Some(selector.select(backend).await?)),
+        None => backend.current_session().await,
+    }
+}
 
+async fn watch(backend: &dyn MediaBackend, args: &Args, events_only: bool) -> Result<()> {
+    let mut events = backend.watch().await?;
+
+    if let Format::Ndjson = args.format {
+        let started = Instant::now();
+        while let Some(event) = events.recv().await {
+            let record = EventRecord::collect(backend, event, started).await;
+            let mut stdout = io::stdout().lock();
+            format::write_ndjson(&mut stdout, &record)?;
+            stdout.flush()?;
+        }
+        return Ok(());
+    }
+
+    let mut watched = None;
+    if !events_only {
+        watched = find_session(backend, args.session.as_ref())
+            .await
+            .ok()
+            .flatten();
+        if let Some(session) = &watched {
+            render(
+                args.format,
+                &SessionSnapshot::collect(session.as_ref()).await,
+            )?;
+        }
+    }
+
+    while let Some(event) = events.recv().await {
+        println!("event: {event}");
+        io::stdout().flush()?;
+        if events_only {
+            continue;
+        }
+
+        let follows_current = args.session.is_none();
+        if matches!(event, MediaEvent::CurrentSessionChanged { .. }) && follows_current
+            || matches!(
+                event,
+                MediaEvent::SessionAdded { .. } | MediaEvent::SessionRemoved { .. }
+            )
+        {
+            watched = find_session(backend, args.session.as_ref())
+                .await
+                .ok()
+                .flatten();
+        }
+
+        let Some(session) = &watched else { continue };
+        if event.session() == Some(session.id()) {
+            render(
+                args.format,
+                &SessionSnapshot::collect(session.as_ref()).await,
+            )?;
+        }
+    }
     Ok(())
 }
 
-fn playback_type_str(t: i32) -> &'static str {
-    match t {
-        0 => "Unknown",
-        1 => "Music",
-        2 => "Video",
-        3 => "Image",
-        _ => unreachable!(),
+fn render(format: Format, value: &impl Render) -> Result<()> {
+    let mut stdout = io::stdout().lock();
+    match format {
+        Format::Text => value.write_text(&mut stdout, TimeStyle::Raw)?,
+        Format::Human => value.write_text(&mut stdout, TimeStyle::Human)?,
+        Format::Json => format::write_json(&mut stdout, value)?,
+        Format::Ndjson => format::write_ndjson(&mut stdout, value)?,
+        Format::Yaml => format::write_yaml(&mut stdout, value)?,
+        Format::Toml => format::write_toml(&mut stdout, value)?,
     }
+    stdout.flush()?;
+    Ok(())
 }
@@ -0,0 +1,103 @@
+//! JSON merge patches (RFC 7386) between serialized values.
+
+use serde_json::{Map, Value};
+
+/// The merge patch turning `from` into `to`, or `None` when they are equal.
+///
+/// Members missing from `to` become `null`, which merge patches use for
+/// removal. Snapshot fields serialize `None` as `null`, so a client applying
+/// the patch sees such a field disappear rather than turn `null`; both read
+/// as "unknown". Arrays are replaced as a whole.
+pub fn merge_patch(from: &Value, to: &Value) -> Option<Value> {
+    if from == to {
+        return None;
+    }
+    let (Value::Object(from), Value::Object(to)) = (from, to) else {
+        return Some(to.clone());
+    };
+
+    let mut patch = Map::new();
+    for key in from.keys().filter(|key| !to.contains_key(*key)) {
+        patch.insert(key.clone(), Value::Null);
+    }
+    for (key, value) in to {
+        let member = match from.get(key) {
+            Some(old) => merge_patch(old, value),
+            None => Some(value.clone()),
+        };
+        if let Some(member) = member {
+            patch.insert(key.clone(), member);
+        }
+    }
+    Some(Value::Object(patch))
+}
+
+/// Applies a merge patch to `target` as RFC 7386 describes, undoing
+/// [`merge_patch`].
+pub fn apply_merge_patch(target: &Value, patch: &Value) -> Value {
+    let Value::Object(patch) = patch else {
+        return patch.clone();
+    };
+    let mut target = match target {
+        Value::Object(target) => target.clone(),
+        _ => Map::new(),
+    };
+    for (key, value) in patch {
+        if value.is_null() {
+            target.remove(key);
+        } else {
+            let old = target.get(key).cloned().unwrap_or(Value::Null);
+            target.insert(key.clone(), apply_merge_patch(&old, value));
+        }
+    }
+    Value::Object(target)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn equal_values_need_no_patch() {
+        let value = json!({ "a": 1, "b": [1, 2] });
+        assert_eq!(merge_patch(&value, &value), None);
+    }
+
+    #[test]
+    fn patches_only_what_changed() {
+        let from = json!({ "a": 1, "b": { "c": 2, "d": 3 } });
+        let to = json!({ "a": 1, "b": { "c": 2, "d": 4 } });
+        assert_eq!(merge_patch(&from, &to), Some(json!({ "b": { "d": 4 } })));
+    }
+
+    #[test]
+    fn removed_members_become_null() {
+        let from = json!({ "a": 1, "b": 2 });
+        let to = json!({ "a": 1 });
+        assert_eq!(merge_patch(&from, &to), Some(json!({ "b": null })));
+    }
+
+    #[test]
+    fn replaces_arrays_and_non_objects_whole() {
+        let from = json!({ "genres": ["Rock", "Pop"], "title": { "x": 1 } });
+        let to = json!({ "genres": ["Rock"], "title": "Song" });
+        assert_eq!(merge_patch(&from, &to), Some(to.clone()));
+        assert_eq!(merge_patch(&json!(1), &json!([1])), Some(json!([1])));
+    }
+
+    #[test]
+    fn applying_the_patch_yields_the_target() {
+        let from = json!({
+            "media_properties": { "title": "A", "genres": ["Rock"], "artist": "X" },
+            "playback_info": { "playback_rate": 1.0 },
+        });
+        let to = json!({
+            "media_properties": { "title": "B", "genres": [] },
+            "playback_info": { "playback_rate": 1.5, "is_shuffle_active": true },
+            "timeline_properties": { "position": 5 },
+        });
+        let patch = merge_patch(&from, &to).unwrap();
+        assert_eq!(apply_merge_patch(&from, &patch), to);
+    }
+}
@@ -0,0 +1,241 @@
+//! Where playback is right now, between the player's rare position updates.
+
+use std::time::{Duration, SystemTime};
+
+use crate::backend::universal_time;
+use crate::snapshot::{PlaybackInfo, PlaybackStatus, TimelineProperties};
+
+/// Extrapolates the playback position from the last reported one.
+///
+/// Players report `position` together with `last_updated_time` and only
+/// refresh it on seeks and state changes. While playing, the position
+/// advances by the elapsed time scaled by the playback rate; it never leaves
+/// `start_time..=end_time`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PositionEstimator {
+    position: Duration,
+    /// 100ns ticks since 1601-01-01 UTC at which `position` was true.
+    updated: i64,
+    playing: bool,
+    rate: f64,
+    start: Duration,
+    end: Option<Duration>,
+}
+
+impl PositionEstimator {
+    /// Starts from a snapshot; `None` without a reported position. A missing
+    /// `last_updated_time` is taken to mean the position is current.
+    pub fn new(
+        timeline: &TimelineProperties,
+        playback_info: Option<&PlaybackInfo>,
+    ) -> Option<Self> {
+        let mut estimator = Self {
+            position: timeline.position?,
+            updated: timeline
+                .last_updated_time
+                .unwrap_or_else(|| universal_time(SystemTime::now())),
+            playing: false,
+            rate: 1.0,
+            start: timeline.start_time.unwrap_or(Duration::ZERO),
+            // Streams without a known length report an end time of zero.
+            end: timeline.end_time.filter(|end| !end.is_zero()),
+        };
+        if let Some(playback_info) = playback_info {
+            estimator.apply_playback_info(playback_info);
+        }
+        Some(estimator)
+    }
+
+    /// The estimated position at `time`.
+    pub fn position_at(&self, time: SystemTime) -> Duration {
+        self.position_at_ticks(universal_time(time))
+    }
+
+    /// The estimated position now.
+    pub fn now(&self) -> Duration {
+        self.position_at(SystemTime::now())
+    }
+
+    /// Re-anchors on a newly reported timeline, as after a seek.
+    pub fn update_timeline(&mut self, timeline: &TimelineProperties) {
+        if let Some(estimator) = Self::new(timeline, None) {
+            *self = Self {
+                playing: self.playing,
+                rate: self.rate,
+                ..estimator
+            };
+        }
+    }
+
+    /// Records a jump to `position` at `time`, as reported by MPRIS `Seeked`.
+    pub fn seeked(&mut self, position: Duration, time: SystemTime) {
+        self.position = position;
+        self.updated = universal_time(time);
+    }
+
+    /// Switches to the status and rate of `playback_info` from `time` on,
+    /// keeping the position reached until then.
+    pub fn playback_changed(&mut self, playback_info: &PlaybackInfo, time: SystemTime) {
+        let ticks = universal_time(time);
+        self.position = self.position_at_ticks(ticks);
+        self.updated = ticks;
+        self.apply_playback_info(playback_info);
+    }
+
+    fn apply_playback_info(&mut self, playback_info: &PlaybackInfo) {
+        if let Some(status) = playback_info.playback_status {
+            self.playing = status == PlaybackStatus::Playing;
+        }
+        if let Some(rate) = playback_info.playback_rate.filter(|rate| rate.is_finite()) {
+            self.rate = rate;
+        }
+    }
+
+    fn position_at_ticks(&self, ticks: i64) -> Duration {
+        let position = if self.playing {
+            let elapsed = ticks.saturating_sub(self.updated).max(0) as f64 * 100.0;
+            let advanced = self.position.as_nanos() as f64 + elapsed * self.rate;
+            Duration::from_nanos(advanced.max(0.0) as u64)
+        } else {
+            self.position
+        };
+
+        let position = position.max(self.start);
+        match self.end {
+            Some(end) => position.min(end),
+            None => position,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(seconds: f64) -> SystemTime {
+        SystemTime::UNIX_EPOCH
+            + Duration::from_secs(1_700_000_000)
+            + Duration::from_secs_f64(seconds)
+    }
+
+    fn timeline(position: u64, end: u64) -> TimelineProperties {
+        TimelineProperties {
+            start_time: Some(Duration::ZERO),
+            end_time: Some(Duration::from_secs(end)),
+            position: Some(Duration::from_secs(position)),
+            last_updated_time: Some(universal_time(at(0.0))),
+            ..Default::default()
+        }
+    }
+
+    fn playback(status: PlaybackStatus, rate: f64) -> PlaybackInfo {
+        PlaybackInfo {
+            playback_status: Some(status),
+            playback_rate: Some(rate),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn advances_while_playing() {
+        let playing = playback(PlaybackStatus::Playing, 1.0);
+        let estimator = PositionEstimator::new(&timeline(30, 180), Some(&playing)).unwrap();
+        assert_eq!(estimator.position_at(at(0.0)), Duration::from_secs(30));
+        assert_eq!(
+            estimator.position_at(at(12.5)),
+            Duration::from_secs_f64(42.5)
+        );
+    }
+
+    #[test]
+    fn holds_while_paused() {
+        let paused = playback(PlaybackStatus::Paused, 1.0);
+        let estimator = PositionEstimator::new(&timeline(30, 180), Some(&paused)).unwrap();
+        assert_eq!(estimator.position_at(at(60.0)), Duration::from_secs(30));
+    }
+
+    #[test]
+    fn pausing_keeps_the_position_reached() {
+        let mut estimator = PositionEstimator::new(
+            &timeline(30, 180),
+            Some(&playback(PlaybackStatus::Playing, 1.0)),
+        )
+        .unwrap();
+        estimator.playback_changed(&playback(PlaybackStatus::Paused, 1.0), at(10.0));
+        assert_eq!(estimator.position_at(at(100.0)), Duration::from_secs(40));
+
+        estimator.playback_changed(&playback(PlaybackStatus::Playing, 1.0), at(100.0));
+        assert_eq!(estimator.position_at(at(105.0)), Duration::from_secs(45));
+    }
+
+    #[test]
+    fn scales_with_playback_rate() {
+        let fast = playback(PlaybackStatus::Playing, 1.5);
+        let estimator = PositionEstimator::new(&timeline(10, 180), Some(&fast)).unwrap();
+        assert_eq!(estimator.position_at(at(10.0)), Duration::from_secs(25));
+
+        let slow = playback(PlaybackStatus::Playing, 0.5);
+        let estimator = PositionEstimator::new(&timeline(10, 180), Some(&slow)).unwrap();
+        assert_eq!(estimator.position_at(at(10.0)), Duration::from_secs(15));
+    }
+
+    #[test]
+    fn rate_change_applies_from_then_on() {
+        let mut estimator = PositionEstimator::new(
+            &timeline(0, 180),
+            Some(&playback(PlaybackStatus::Playing, 1.0)),
+        )
+        .unwrap();
+        estimator.playback_changed(&playback(PlaybackStatus::Playing, 2.0), at(10.0));
+        assert_eq!(estimator.position_at(at(15.0)), Duration::from_secs(20));
+    }
+
+    #[test]
+    fn seeking_reanchors() {
+        let mut estimator = PositionEstimator::new(
+            &timeline(30, 180),
+            Some(&playback(PlaybackStatus::Playing, 1.0)),
+        )
+        .unwrap();
+        estimator.seeked(Duration::from_secs(120), at(5.0));
+        assert_eq!(estimator.position_at(at(7.0)), Duration::from_secs(122));
+
+        let mut moved = timeline(10, 180);
+        moved.last_updated_time = Some(universal_time(at(20.0)));
+        estimator.update_timeline(&moved);
+        assert_eq!(estimator.position_at(at(25.0)), Duration::from_secs(15));
+    }
+
+    #[test]
+    fn clamps_to_end_time() {
+        let playing = playback(PlaybackStatus::Playing, 1.0);
+        let estimator = PositionEstimator::new(&timeline(170, 180), Some(&playing)).unwrap();
+        assert_eq!(estimator.position_at(at(60.0)), Duration::from_secs(180));
+    }
+
+    #[test]
+    fn clamps_to_start_time() {
+        let rewinding = playback(PlaybackStatus::Playing, -2.0);
+        let estimator = PositionEstimator::new(&timeline(10, 180), Some(&rewinding)).unwrap();
+        assert_eq!(estimator.position_at(at(60.0)), Duration::ZERO);
+    }
+
+    #[test]
+    fn ignores_time_before_the_update() {
+        let playing = playback(PlaybackStatus::Playing, 1.0);
+        let estimator = PositionEstimator::new(&timeline(30, 180), Some(&playing)).unwrap();
+        assert_eq!(
+            estimator.position_at(at(0.0) - Duration::from_secs(5)),
+            Duration::from_secs(30)
+        );
+    }
+
+    #[test]
+    fn unknown_end_does_not_clamp() {
+        let mut live = timeline(30, 0);
+        live.end_time = None;
+        let playing = playback(PlaybackStatus::Playing, 1.0);
+        let estimator = PositionEstimator::new(&live, Some(&playing)).unwrap();
+        assert_eq!(estimator.position_at(at(1000.0)), Duration::from_secs(1030));
+    }
+}
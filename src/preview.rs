@@ -0,0 +1,279 @@
+//! Drawing cover art in the terminal.
+
+use std::env;
+use std::fmt;
+use std::io::{Cursor, Write};
+use std::str::FromStr;
+
+use anyhow::{bail, Context, Result};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use image::imageops::FilterType;
+use image::{DynamicImage, ImageFormat, RgbImage};
+
+use crate::thumbnail::ThumbnailData;
+
+/// How an image reaches the terminal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Protocol {
+    /// Guess from the environment, see [`Protocol::detect`].
+    Auto,
+    /// `▀` cells with a truecolor foreground and background, two pixels each.
+    Blocks,
+    /// The kitty graphics protocol.
+    Kitty,
+    /// DEC sixel graphics.
+    Sixel,
+}
+
+impl Protocol {
+    /// Picks the best protocol the terminal advertises through its
+    /// environment, falling back to half blocks. Terminals are not queried,
+    /// so sixel support is only assumed when `TERM` mentions it.
+    pub fn detect() -> Self {
+        let term = env::var("TERM").unwrap_or_default();
+        let term_program = env::var("TERM_PROGRAM").unwrap_or_default();
+        if env::var_os("KITTY_WINDOW_ID").is_some()
+            || term.contains("kitty")
+            || term.contains("ghostty")
+            || matches!(term_program.as_str(), "WezTerm" | "ghostty")
+        {
+            Self::Kitty
+        } else if term.contains("sixel") || term == "mlterm" || term.starts_with("foot") {
+            Self::Sixel
+        } else {
+            Self::Blocks
+        }
+    }
+}
+
+impl FromStr for Protocol {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "auto" => Ok(Self::Auto),
+            "blocks" => Ok(Self::Blocks),
+            "kitty" => Ok(Self::Kitty),
+            "sixel" => Ok(Self::Sixel),
+            _ => bail!("unknown protocol {s:?}, expected one of: auto, blocks, kitty, sixel"),
+        }
+    }
+}
+
+impl fmt::Display for Protocol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Auto => "auto",
+            Self::Blocks => "blocks",
+            Self::Kitty => "kitty",
+            Self::Sixel => "sixel",
+        })
+    }
+}
+
+/// Pixels per terminal column assumed when sizing sixel images, which are
+/// placed in pixels rather than cells.
+const SIXEL_COLUMN_WIDTH: u32 = 8;
+
+/// Draws `thumbnail` `columns` terminal cells wide, keeping its aspect ratio.
+pub fn draw(
+    w: &mut dyn Write,
+    thumbnail: &ThumbnailData,
+    protocol: Protocol,
+    columns: u32,
+) -> Result<()> {
+    if columns == 0 {
+        bail!("the preview must be at least one column wide");
+    }
+    let image = image::load_from_memory(&thumbnail.bytes).context("could not decode thumbnail")?;
+    let protocol = match protocol {
+        Protocol::Auto => Protocol::detect(),
+        protocol => protocol,
+    };
+
+    match protocol {
+        Protocol::Auto | Protocol::Blocks => draw_blocks(w, &scale(&image, columns)),
+        Protocol::Kitty => draw_kitty(w, &image, columns),
+        Protocol::Sixel => {
+            let width = columns
+                .checked_mul(SIXEL_COLUMN_WIDTH)
+                .with_context(|| format!("a preview {columns} columns wide is too wide"))?;
+            draw_sixel(w, &scale(&image, width))
+        }
+    }
+}
+
+/// Resizes to `width` pixels, keeping the aspect ratio.
+fn scale(image: &DynamicImage, width: u32) -> RgbImage {
+    let height = (image.height() as u64 * width as u64 / image.width().max(1) as u64).max(1);
+    image
+        .resize_exact(width, height as u32, FilterType::Triangle)
+        .to_rgb8()
+}
+
+fn draw_blocks(w: &mut dyn Write, image: &RgbImage) -> Result<()> {
+    for y in (0..image.height()).step_by(2) {
+        for x in 0..image.width() {
+            let [r, g, b] = image.get_pixel(x, y).0;
+            write!(w, "\x1b[38;2;{r};{g};{b}m")?;
+            // An odd last row leaves the lower half of the cell empty.
+            match (y + 1 < image.height()).then(|| image.get_pixel(x, y + 1).0) {
+                Some([r, g, b]) => write!(w, "\x1b[48;2;{r};{g};{b}m▀")?,
+                None => write!(w, "\x1b[49m▀")?,
+            }
+        }
+        writeln!(w, "\x1b[0m")?;
+    }
+    Ok(())
+}
+
+/// Sends the image as PNG and lets the terminal scale it to `columns`.
+fn draw_kitty(w: &mut dyn Write, image: &DynamicImage, columns: u32) -> Result<()> {
+    let mut png = vec![];
+    image.write_to(&mut Cursor::new(&mut png), ImageFormat::Png)?;
+    let payload = STANDARD.encode(png);
+
+    // Payloads are sent in chunks of at most 4096 bytes; `m=1` marks all but
+    // the last one.
+    let chunks = payload.as_bytes().chunks(4096).collect::<Vec<_>>();
+    for (index, chunk) in chunks.iter().enumerate() {
+        let more = u8::from(index + 1 < chunks.len());
+        let chunk = std::str::from_utf8(chunk)?;
+        if index == 0 {
+            write!(w, "\x1b_Ga=T,f=100,c={columns},m={more};{chunk}\x1b\\")?;
+        } else {
+            write!(w, "\x1b_Gm={more};{chunk}\x1b\\")?;
+        }
+    }
+    writeln!(w)?;
+    Ok(())
+}
+
+/// Encodes the image as sixels over a 6×6×6 color cube.
+fn draw_sixel(w: &mut dyn Write, image: &RgbImage) -> Result<()> {
+    let (width, height) = image.dimensions();
+    let level = |value: u8| (value as u32 * 5 + 127) / 255;
+    let color = |x: u32, y: u32| {
+        let [r, g, b] = image.get_pixel(x, y).0;
+        (level(r) * 36 + level(g) * 6 + level(b)) as usize
+    };
+
+    write!(w, "\x1bPq\"1;1;{width};{height}")?;
+    for index in 0..216 {
+        let percent = |step: usize| step * 100 / 5;
+        let (r, g, b) = (index / 36, index / 6 % 6, index % 6);
+        write!(w, "#{index};2;{};{};{}", percent(r), percent(g), percent(b))?;
+    }
+
+    for band in (0..height).step_by(6) {
+        let rows = (height - band).min(6);
+        let mut used = vec![false; 216];
+        for y in band..band + rows {
+            for x in 0..width {
+                used[color(x, y)] = true;
+            }
+        }
+
+        let mut first = true;
+        for index in (0..216).filter(|&index| used[index]) {
+            if !first {
+                write!(w, "$")?;
+            }
+            first = false;
+            write!(w, "#{index}")?;
+
+            let sixels = (0..width).map(|x| {
+                let bits = (0..rows)
+                    .filter(|&row| color(x, band + row) == index)
+                    .fold(0u8, |bits, row| bits | 1 << row);
+                (0x3f + bits) as char
+            });
+            write_runs(w, sixels)?;
+        }
+        write!(w, "-")?;
+    }
+    writeln!(w, "\x1b\\")?;
+    Ok(())
+}
+
+/// Writes sixel characters, folding repeats into `!<count><char>`.
+fn write_runs(w: &mut dyn Write, sixels: impl Iterator<Item = char>) -> Result<()> {
+    let mut run: Option<(char, usize)> = None;
+    for sixel in sixels.map(Some).chain([None]) {
+        match (run, sixel) {
+            (Some((current, count)), Some(next)) if current == next => {
+                run = Some((current, count + 1));
+                continue;
+            }
+            (Some((current, count)), _) if count > 3 => write!(w, "!{count}{current}")?,
+            (Some((current, count)), _) => write!(w, "{}", current.to_string().repeat(count))?,
+            (None, _) => {}
+        }
+        run = sixel.map(|sixel| (sixel, 1));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use image::Rgb;
+
+    use super::*;
+
+    const RED: Rgb<u8> = Rgb([255, 0, 0]);
+    const BLUE: Rgb<u8> = Rgb([0, 0, 255]);
+
+    fn runs(sixels: &str) -> String {
+        let mut output = vec![];
+        write_runs(&mut output, sixels.chars()).unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn folds_runs_longer_than_three() {
+        assert_eq!(runs(""), "");
+        assert_eq!(runs("???"), "???");
+        assert_eq!(runs("????"), "!4?");
+        assert_eq!(runs("~~~~~~@@~"), "!6~@@~");
+        assert_eq!(runs("A!!!!!B"), "A!5!B");
+    }
+
+    #[test]
+    fn scales_to_the_aspect_ratio() {
+        let wide = DynamicImage::ImageRgb8(RgbImage::new(40, 10));
+        assert_eq!(scale(&wide, 8).dimensions(), (8, 2));
+        let tall = DynamicImage::ImageRgb8(RgbImage::new(10, 40));
+        assert_eq!(scale(&tall, 5).dimensions(), (5, 20));
+        let strip = DynamicImage::ImageRgb8(RgbImage::new(100, 1));
+        assert_eq!(scale(&strip, 10).dimensions(), (10, 1));
+    }
+
+    #[test]
+    fn leaves_the_lower_half_of_an_odd_last_row_empty() {
+        let mut image = RgbImage::from_pixel(1, 3, RED);
+        image.put_pixel(0, 1, BLUE);
+        let mut output = vec![];
+        draw_blocks(&mut output, &image).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "\x1b[38;2;255;0;0m\x1b[48;2;0;0;255m▀\x1b[0m\n\
+             \x1b[38;2;255;0;0m\x1b[49m▀\x1b[0m\n"
+        );
+    }
+
+    #[test]
+    fn refuses_widths_that_overflow() {
+        let mut png = vec![];
+        DynamicImage::ImageRgb8(RgbImage::new(1, 1))
+            .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+            .unwrap();
+        let thumbnail = ThumbnailData {
+            content_type: None,
+            bytes: png,
+        };
+        let error = draw(&mut vec![], &thumbnail, Protocol::Sixel, u32::MAX).unwrap_err();
+        assert!(error.to_string().contains("too wide"), "{error}");
+        assert!(draw(&mut vec![], &thumbnail, Protocol::Blocks, 0).is_err());
+    }
+}
@@ -0,0 +1,175 @@
+//! Choosing a session by its identifier.
+
+use std::fmt;
+use std::str::FromStr;
+
+use anyhow::{anyhow, bail, Result};
+use regex::Regex;
+
+use crate::backend::{MediaBackend, MediaSession, MPRIS_BUS_NAME_PREFIX};
+
+/// Matches session identifiers (`SourceAppUserModelId` or MPRIS bus name).
+///
+/// Parsed from `re:<regex>`, `glob:<pattern>` or `exact:<id>`; without a
+/// prefix, a pattern containing `*`, `?` or `[` is a glob and anything else
+/// must match exactly. Exact ids may leave out the MPRIS bus name prefix, so
+/// `spotify` selects `org.mpris.MediaPlayer2.spotify`.
+#[derive(Debug, Clone)]
+pub enum SessionSelector {
+    Exact(String),
+    Glob(glob::Pattern),
+    Regex(Regex),
+}
+
+impl SessionSelector {
+    pub fn matches(&self, id: &str) -> bool {
+        match self {
+            Self::Exact(exact) => {
+                id == exact || id.strip_prefix(MPRIS_BUS_NAME_PREFIX) == Some(exact)
+            }
+            Self::Glob(pattern) => pattern.matches(id),
+            Self::Regex(regex) => regex.is_match(id),
+        }
+    }
+
+    /// Returns the first session of `backend` whose id matches.
+    pub async fn select(&self, backend: &dyn MediaBackend) -> Result<Box<dyn MediaSession>> {
+        let sessions = backend.sessions().await?;
+        let ids = sessions
+            .iter()
+            .map(|session| session.id().to_string())
+            .collect::<Vec<_>>();
+
+        if let Some(session) = sessions
+            .into_iter()
+            .find(|session| self.matches(session.id()))
+        {
+            return Ok(session);
+        }
+
+        if ids.is_empty() {
+            bail!("no session matches {self}: no media sessions are available");
+        }
+        bail!(
+            "no session matches {self}; available sessions:\n{}",
+            ids.iter()
+                .map(|id| format!("  {id}"))
+                .collect::<Vec<_>>()
+                .join("\n")
+        )
+    }
+}
+
+impl FromStr for SessionSelector {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        if let Some(regex) = s.strip_prefix("re:") {
+            return Ok(Self::Regex(
+                Regex::new(regex).map_err(|e| anyhow!("invalid regex {regex:?}: {e}"))?,
+            ));
+        }
+        if let Some(pattern) = s.strip_prefix("glob:") {
+            return glob_selector(pattern);
+        }
+        if let Some(exact) = s.strip_prefix("exact:") {
+            return Ok(Self::Exact(exact.to_string()));
+        }
+        if s.contains(['*', '?', '[']) {
+            return glob_selector(s);
+        }
+        Ok(Self::Exact(s.to_string()))
+    }
+}
+
+fn glob_selector(pattern: &str) -> Result<SessionSelector> {
+    Ok(SessionSelector::Glob(
+        glob::Pattern::new(pattern).map_err(|e| anyhow!("invalid glob {pattern:?}: {e}"))?,
+    ))
+}
+
+impl fmt::Display for SessionSelector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Exact(exact) => write!(f, "{exact:?}"),
+            Self::Glob(pattern) => write!(f, "glob {:?}", pattern.as_str()),
+            Self::Regex(regex) => write!(f, "regex {:?}", regex.as_str()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::mock::{MockBackend, MockSessionState, MockState};
+
+    fn selector(s: &str) -> SessionSelector {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn parses_prefixes() {
+        assert!(matches!(selector("re:^spot"), SessionSelector::Regex(_)));
+        assert!(matches!(selector("glob:Spotify"), SessionSelector::Glob(_)));
+        assert!(matches!(selector("exact:*.exe"), SessionSelector::Exact(id) if id == "*.exe"));
+        assert!("re:(".parse::<SessionSelector>().is_err());
+        assert!("glob:[".parse::<SessionSelector>().is_err());
+    }
+
+    #[test]
+    fn detects_globs() {
+        for pattern in ["*.exe", "vlc?", "[Ss]potify"] {
+            assert!(
+                matches!(selector(pattern), SessionSelector::Glob(_)),
+                "{pattern}"
+            );
+        }
+        assert!(matches!(selector("Spotify.exe"), SessionSelector::Exact(_)));
+        assert!(selector("*.exe").matches("Spotify.exe"));
+        assert!(selector("[Ss]potify").matches("spotify"));
+        assert!(!selector("vlc?").matches("vlc"));
+    }
+
+    #[test]
+    fn matches_exact_ids_with_or_without_the_mpris_prefix() {
+        let spotify = selector("spotify");
+        assert!(spotify.matches("spotify"));
+        assert!(spotify.matches("org.mpris.MediaPlayer2.spotify"));
+        assert!(!spotify.matches("org.mpris.MediaPlayer2.spotify.instance42"));
+        assert!(!spotify.matches("Spotify.exe"));
+        assert!(selector("org.mpris.MediaPlayer2.vlc").matches("org.mpris.MediaPlayer2.vlc"));
+        assert!(selector("exact:vlc").matches("org.mpris.MediaPlayer2.vlc"));
+        assert!(selector("re:^org\\.mpris\\.").matches("org.mpris.MediaPlayer2.vlc"));
+    }
+
+    #[tokio::test]
+    async fn selects_the_first_match_or_lists_the_sessions() {
+        let backend = MockBackend::new(MockState {
+            sessions: vec![
+                MockSessionState::new("org.mpris.MediaPlayer2.vlc"),
+                MockSessionState::new("Spotify.exe"),
+                MockSessionState::new("org.mpris.MediaPlayer2.mpv"),
+            ],
+        });
+        let found = selector("org.mpris.*").select(&backend).await.unwrap();
+        assert_eq!(found.id(), "org.mpris.MediaPlayer2.vlc");
+
+        let Err(error) = selector("firefox").select(&backend).await else {
+            panic!("firefox was found");
+        };
+        assert_eq!(
+            error.to_string(),
+            "no session matches \"firefox\"; available sessions:\n  \
+             org.mpris.MediaPlayer2.vlc\n  Spotify.exe\n  org.mpris.MediaPlayer2.mpv"
+        );
+
+        let empty = MockBackend::default();
+        let Err(error) = selector("glob:*").select(&empty).await else {
+            panic!("a session was found in an empty backend");
+        };
+        assert_eq!(
+            error.to_string(),
+            "no session matches glob \"*\": no media sessions are available"
+        );
+    }
+}
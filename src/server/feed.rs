@@ -0,0 +1,293 @@
+//! The WebSocket feed: a snapshot on connect, then merge patches and
+//! extrapolated position ticks.
+
+use std::future;
+use std::time::Duration;
+
+use anyhow::Result;
+use axum::extract::ws::{Message, WebSocket};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::time::{self, Interval, MissedTickBehavior};
+
+use super::Api;
+use crate::backend::{MediaEvent, MediaSession};
+use crate::patch::merge_patch;
+use crate::position::PositionEstimator;
+use crate::snapshot::{nanos, SessionSnapshot};
+
+/// One text frame of the feed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum FeedMessage {
+    /// The whole snapshot, sent on connect and whenever the feed moves to
+    /// another session; `null` while there is none to follow.
+    Snapshot {
+        snapshot: Option<Box<SessionSnapshot>>,
+    },
+    /// A merge patch to apply to the last snapshot, see
+    /// [`crate::patch::merge_patch`].
+    Patch { session: String, patch: Value },
+    /// Where playback is now, extrapolated from the last reported position.
+    Position {
+        session: String,
+        #[serde(with = "nanos")]
+        position: Duration,
+    },
+}
+
+/// The session a connection follows and what it last sent about it.
+struct Feed {
+    api: Api,
+    /// The `{id}` path segment; `current` follows the current session.
+    id: String,
+    session: Option<Box<dyn MediaSession>>,
+    snapshot: Value,
+    estimator: Option<PositionEstimator>,
+    position: Option<Duration>,
+}
+
+/// Runs the feed for `id` until the client disconnects. `tick` is the
+/// interval of position messages; zero turns them off.
+pub(super) async fn run(api: Api, id: String, tick: Duration, mut socket: WebSocket) {
+    // Errors mean the client went away or the backend cannot be watched;
+    // either way there is nobody left to tell.
+    let _ = feed(api, id, tick, &mut socket).await;
+}
+
+async fn feed(api: Api, id: String, tick: Duration, socket: &mut WebSocket) -> Result<()> {
+    let mut events = api.backend.watch().await?;
+    let mut feed = Feed {
+        api,
+        id,
+        session: None,
+        snapshot: Value::Null,
+        estimator: None,
+        position: None,
+    };
+    feed.resolve(socket).await?;
+
+    let mut ticker = (!tick.is_zero()).then(|| {
+        let mut ticker = time::interval(tick);
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Skip);
+        ticker
+    });
+
+    loop {
+        tokio::select! {
+            event = events.recv() => match event {
+                Some(event) => feed.handle(&event, socket).await?,
+                None => return Ok(()),
+            },
+            _ = next_tick(&mut ticker) => feed.tick(socket).await?,
+            message = socket.recv() => match message {
+                Some(Ok(Message::Close(_)) | Err(_)) | None => return Ok(()),
+                Some(Ok(_)) => {}
+            },
+        }
+    }
+}
+
+async fn next_tick(ticker: &mut Option<Interval>) {
+    match ticker {
+        Some(ticker) => {
+            ticker.tick().await;
+        }
+        None => future::pending().await,
+    }
+}
+
+impl Feed {
+    async fn handle(&mut self, event: &MediaEvent, socket: &mut WebSocket) -> Result<()> {
+        let follows_current = self.id == "current" && self.api.current.is_none();
+        let moves = match event {
+            MediaEvent::CurrentSessionChanged { .. } => follows_current,
+            MediaEvent::SessionAdded { .. } | MediaEvent::SessionRemoved { .. } => true,
+            _ => false,
+        };
+        if moves {
+            return self.resolve(socket).await;
+        }
+        let Some(session) = &self.session else {
+            return Ok(());
+        };
+        if event.session() != Some(session.id()) {
+            return Ok(());
+        }
+        self.refresh(socket).await
+    }
+
+    /// Looks the followed session up again, sending a full snapshot when it
+    /// is not the one followed so far and a patch otherwise.
+    async fn resolve(&mut self, socket: &mut WebSocket) -> Result<()> {
+        let session = self.api.session(&self.id).await.ok();
+        let same = match (&session, &self.session) {
+            (Some(new), Some(old)) => new.id() == old.id(),
+            _ => false,
+        };
+        self.session = session;
+        if same {
+            return self.refresh(socket).await;
+        }
+
+        self.position = None;
+        let Some(session) = &self.session else {
+            self.snapshot = Value::Null;
+            self.estimator = None;
+            return send(socket, &FeedMessage::Snapshot { snapshot: None }).await;
+        };
+        let snapshot = SessionSnapshot::collect(session.as_ref()).await;
+        self.remember(&snapshot, serde_json::to_value(&snapshot)?);
+        let message = FeedMessage::Snapshot {
+            snapshot: Some(Box::new(snapshot)),
+        };
+        send(socket, &message).await
+    }
+
+    /// Reads the followed session again and sends what changed, if anything.
+    async fn refresh(&mut self, socket: &mut WebSocket) -> Result<()> {
+        let Some(session) = &self.session else {
+            return Ok(());
+        };
+        let snapshot = SessionSnapshot::collect(session.as_ref()).await;
+        let value = serde_json::to_value(&snapshot)?;
+        let patch = merge_patch(&self.snapshot, &value);
+        self.remember(&snapshot, value);
+        match patch {
+            Some(patch) => {
+                let session = snapshot.app_user_model_id;
+                send(socket, &FeedMessage::Patch { session, patch }).await
+            }
+            None => Ok(()),
+        }
+    }
+
+    fn remember(&mut self, snapshot: &SessionSnapshot, value: Value) {
+        self.snapshot = value;
+        self.estimator = snapshot
+            .timeline_properties
+            .as_ref()
+            .and_then(|timeline| PositionEstimator::new(timeline, snapshot.playback_info.as_ref()));
+    }
+
+    /// Sends the estimated position unless it is the one sent last, as it
+    /// is while paused.
+    async fn tick(&mut self, socket: &mut WebSocket) -> Result<()> {
+        let (Some(session), Some(estimator)) = (&self.session, &self.estimator) else {
+            return Ok(());
+        };
+        let position = estimator.now();
+        if self.position == Some(position) {
+            return Ok(());
+        }
+        self.position = Some(position);
+        let message = FeedMessage::Position {
+            session: session.id().to_string(),
+            position,
+        };
+        send(socket, &message).await
+    }
+}
+
+async fn send(socket: &mut WebSocket, message: &FeedMessage) -> Result<()> {
+    let text = serde_json::to_string(message)?;
+    socket.send(Message::Text(text.into())).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use futures_util::StreamExt;
+    use serde_json::json;
+    use tokio::net::TcpListener;
+    use tokio_tungstenite::tungstenite::{self, client::IntoClientRequest};
+
+    use super::*;
+    use crate::backend::mock::{MockBackend, MockSessionState, MockState};
+    use crate::server::router;
+    use crate::snapshot::PlaybackInfo;
+
+    /// Serves the API for `backend` on a free port and returns the address.
+    async fn serve(backend: &MockBackend) -> std::net::SocketAddr {
+        let api = Api {
+            backend: Arc::new(backend.clone()),
+            current: None,
+            tick: Duration::ZERO,
+        };
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, router(api)).await });
+        address
+    }
+
+    #[tokio::test]
+    async fn sends_a_snapshot_then_patches() {
+        let mut session = MockSessionState::new("player");
+        session.is_current = true;
+        session.snapshot.playback_info = Some(PlaybackInfo {
+            playback_rate: Some(1.0),
+            ..Default::default()
+        });
+        let backend = MockBackend::new(MockState {
+            sessions: vec![session],
+        });
+        let address = serve(&backend).await;
+
+        let url = format!("ws://{address}/sessions/current/feed");
+        let (mut socket, _) = tokio_tungstenite::connect_async(url).await.unwrap();
+        let mut next = async || -> FeedMessage {
+            let frame = time::timeout(Duration::from_secs(5), socket.next())
+                .await
+                .expect("feed went quiet")
+                .unwrap()
+                .unwrap();
+            let tungstenite::Message::Text(text) = frame else {
+                panic!("unexpected frame {frame:?}");
+            };
+            serde_json::from_str(&text).unwrap()
+        };
+
+        let FeedMessage::Snapshot {
+            snapshot: Some(snapshot),
+        } = next().await
+        else {
+            panic!("feed did not start with a snapshot");
+        };
+        assert_eq!(snapshot.app_user_model_id, "player");
+
+        backend
+            .update("player", |snapshot| {
+                snapshot.playback_info.as_mut().unwrap().playback_rate = Some(1.5);
+            })
+            .unwrap();
+        let FeedMessage::Patch { session, patch } = next().await else {
+            panic!("change was not sent as a patch");
+        };
+        assert_eq!(session, "player");
+        assert_eq!(patch, json!({ "playback_info": { "playback_rate": 1.5 } }));
+    }
+
+    #[tokio::test]
+    async fn refuses_pages_from_other_sites() {
+        let address = serve(&MockBackend::default()).await;
+        let url = format!("ws://{address}/sessions/current/feed");
+        let mut request = url.as_str().into_client_request().unwrap();
+        request
+            .headers_mut()
+            .insert("origin", "https://example.com".parse().unwrap());
+        let Err(tungstenite::Error::Http(response)) =
+            tokio_tungstenite::connect_async(request).await
+        else {
+            panic!("feed accepted a foreign origin");
+        };
+        assert_eq!(response.status(), 403);
+
+        let mut request = url.as_str().into_client_request().unwrap();
+        request
+            .headers_mut()
+            .insert("origin", format!("http://{address}").parse().unwrap());
+        assert!(tokio_tungstenite::connect_async(request).await.is_ok());
+    }
+}
@@ -0,0 +1,528 @@
+//! A small HTTP API over the backend, for widgets that cannot link the
+//! platform code themselves.
+//!
+//! Every response is JSON except the thumbnail, and every session route
+//! accepts `current` in place of a session id. `/sessions/{id}/feed` is a
+//! WebSocket pushing changes as they happen; see [`feed`].
+//!
+//! Browsers let any page post a form to the control routes and open the
+//! feed, so those refuse requests carrying an `Origin` other than this
+//! machine.
+
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use axum::extract::{Path, Query, Request, State, WebSocketUpgrade};
+use axum::http::{header, StatusCode, Uri};
+use axum::middleware::{self, Next};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post, MethodRouter};
+use axum::{Json, Router};
+use serde_json::json;
+use tokio::net::TcpListener;
+
+use crate::backend::{MediaBackend, MediaSession};
+use crate::control::{self, CommandOutcome, PlaybackCommand, SeekOffset};
+use crate::duration;
+use crate::selector::SessionSelector;
+use crate::snapshot::{RepeatMode, SessionList, SessionSnapshot};
+use crate::thumbnail::sniff_content_type;
+
+pub mod feed;
+
+/// What the handlers share.
+#[derive(Clone)]
+struct Api {
+    backend: Arc<dyn MediaBackend>,
+    /// Picks the session behind `current` instead of the backend.
+    current: Option<SessionSelector>,
+    /// Default interval of the feed's position messages.
+    tick: Duration,
+}
+
+/// Serves the API on `listen` until the process ends. `current` overrides
+/// which session `/sessions/current` refers to, like `--session` does for
+/// the other commands, and `tick` is how often feeds send the position
+/// unless a client asks otherwise.
+pub async fn serve(
+    backend: Arc<dyn MediaBackend>,
+    current: Option<SessionSelector>,
+    tick: Duration,
+    listen: SocketAddr,
+) -> Result<()> {
+    let listener = TcpListener::bind(listen)
+        .await
+        .map_err(|e| anyhow!("could not listen on {listen}: {e}"))?;
+    eprintln!("listening on http://{}", listener.local_addr()?);
+    serve_on(listener, backend, current, tick).await
+}
+
+/// Like [`serve`], on a listener bound already.
+pub async fn serve_on(
+    listener: TcpListener,
+    backend: Arc<dyn MediaBackend>,
+    current: Option<SessionSelector>,
+    tick: Duration,
+) -> Result<()> {
+    axum::serve(
+        listener,
+        router(Api {
+            backend,
+            current,
+            tick,
+        }),
+    )
+    .await?;
+    Ok(())
+}
+
+fn router(api: Api) -> Router {
+    let local = Router::new()
+        .route("/sessions/{id}/feed", get(feed))
+        .route("/sessions/{id}/play", command(PlaybackCommand::Play))
+        .route("/sessions/{id}/pause", command(PlaybackCommand::Pause))
+        .route(
+            "/sessions/{id}/toggle",
+            command(PlaybackCommand::TogglePlayPause),
+        )
+        .route("/sessions/{id}/next", command(PlaybackCommand::Next))
+        .route(
+            "/sessions/{id}/previous",
+            command(PlaybackCommand::Previous),
+        )
+        .route("/sessions/{id}/stop", command(PlaybackCommand::Stop))
+        .route("/sessions/{id}/seek", post(seek))
+        .route("/sessions/{id}/position", post(position))
+        .route("/sessions/{id}/repeat", post(repeat))
+        .route("/sessions/{id}/shuffle", post(shuffle))
+        .route("/sessions/{id}/rate", post(rate))
+        .route_layer(middleware::from_fn(local_origin));
+
+    Router::new()
+        .route("/sessions", get(sessions))
+        .route("/sessions/{id}", get(session))
+        .route("/sessions/{id}/thumbnail", get(thumbnail))
+        .merge(local)
+        .fallback(|| async { ApiError::not_found(anyhow!("no such endpoint")) })
+        .with_state(api)
+}
+
+/// Refuses requests made by pages from other hosts. Browsers send `Origin`
+/// with every cross-site `POST` and WebSocket handshake; clients that are
+/// not browsers leave it out and are let through.
+async fn local_origin(request: Request, next: Next) -> Response {
+    if let Some(origin) = request.headers().get(header::ORIGIN) {
+        let local = origin
+            .to_str()
+            .ok()
+            .and_then(|origin| origin.parse::<Uri>().ok())
+            .is_some_and(|origin| origin.host().is_some_and(is_local_host));
+        if !local {
+            let origin = String::from_utf8_lossy(origin.as_bytes());
+            return ApiError::forbidden(anyhow!("requests from {origin} are not allowed"))
+                .into_response();
+        }
+    }
+    next.run(request).await
+}
+
+/// Whether `host` names this machine: `localhost` or a loopback address.
+fn is_local_host(host: &str) -> bool {
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    host.eq_ignore_ascii_case("localhost")
+        || host.parse::<IpAddr>().is_ok_and(|ip| ip.is_loopback())
+}
+
+/// An error response: the status and a `{"error": message}` body.
+struct ApiError(StatusCode, anyhow::Error);
+
+impl ApiError {
+    fn bad_request(error: anyhow::Error) -> Self {
+        Self(StatusCode::BAD_REQUEST, error)
+    }
+
+    fn forbidden(error: anyhow::Error) -> Self {
+        Self(StatusCode::FORBIDDEN, error)
+    }
+
+    fn not_found(error: anyhow::Error) -> Self {
+        Self(StatusCode::NOT_FOUND, error)
+    }
+
+    /// The session refused or could not carry out a control request.
+    fn conflict(error: anyhow::Error) -> Self {
+        Self(StatusCode::CONFLICT, error)
+    }
+
+    fn internal(error: anyhow::Error) -> Self {
+        Self(StatusCode::INTERNAL_SERVER_ERROR, error)
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let body = json!({ "error": format!("{:#}", self.1) });
+        (self.0, Json(body)).into_response()
+    }
+}
+
+type ApiResult<T> = Result<T, ApiError>;
+
+impl Api {
+    /// Resolves a path segment to a session: `current` or an exact id, which
+    /// may leave out the MPRIS bus name prefix.
+    async fn session(&self, id: &str) -> ApiResult<Box<dyn MediaSession>> {
+        if id != "current" {
+            return SessionSelector::Exact(id.to_string())
+                .select(self.backend.as_ref())
+                .await
+                .map_err(ApiError::not_found);
+        }
+        match &self.current {
+            Some(selector) => selector
+                .select(self.backend.as_ref())
+                .await
+                .map_err(ApiError::not_found),
+            None => self
+                .backend
+                .current_session()
+                .await
+                .map_err(ApiError::internal)?
+                .ok_or_else(|| ApiError::not_found(anyhow!("no media session is active"))),
+        }
+    }
+}
+
+async fn sessions(State(api): State<Api>) -> ApiResult<Json<SessionList>> {
+    let sessions = SessionList::collect(api.backend.as_ref())
+        .await
+        .map_err(ApiError::internal)?;
+    Ok(Json(sessions))
+}
+
+async fn session(
+    State(api): State<Api>,
+    Path(id): Path<String>,
+) -> ApiResult<Json<SessionSnapshot>> {
+    let session = api.session(&id).await?;
+    Ok(Json(SessionSnapshot::collect(session.as_ref()).await))
+}
+
+/// The cover art bytes, typed by their magic bytes or else by what the
+/// platform reported.
+async fn thumbnail(State(api): State<Api>, Path(id): Path<String>) -> ApiResult<Response> {
+    let session = api.session(&id).await?;
+    let thumbnail = session
+        .thumbnail()
+        .await
+        .map_err(ApiError::internal)?
+        .ok_or_else(|| ApiError::not_found(anyhow!("{} has no thumbnail", session.id())))?;
+    let content_type = sniff_content_type(&thumbnail.bytes)
+        .map(str::to_string)
+        .or(thumbnail.content_type)
+        .unwrap_or_else(|| "application/octet-stream".to_string());
+    Ok(([(header::CONTENT_TYPE, content_type)], thumbnail.bytes).into_response())
+}
+
+/// Upgrades to the WebSocket feed. `?tick=100ms` sets the interval of
+/// position messages for this connection; `0` turns them off.
+async fn feed(
+    State(api): State<Api>,
+    Path(id): Path<String>,
+    Query(query): Query<HashMap<String, String>>,
+    upgrade: WebSocketUpgrade,
+) -> ApiResult<Response> {
+    let tick = match query.get("tick") {
+        Some(tick) => duration::parse(tick).map_err(ApiError::bad_request)?,
+        None => api.tick,
+    };
+    // Fail before upgrading when the session does not exist; `current`
+    // may come and go, so the feed starts without one.
+    if id != "current" {
+        api.session(&id).await?;
+    }
+    Ok(upgrade.on_upgrade(move |socket| feed::run(api, id, tick, socket)))
+}
+
+/// A `POST` route sending `command`.
+fn command(command: PlaybackCommand) -> MethodRouter<Api> {
+    post(
+        move |State(api): State<Api>, Path(id): Path<String>| async move {
+            let session = api.session(&id).await?;
+            outcome(control::send(session.as_ref(), command).await)
+        },
+    )
+}
+
+// Setters take their value from the query string, e.g.
+// `POST /sessions/current/seek?offset=-10s`. A `+` in a query string reads
+// as a space, which seeks forward just the same.
+
+async fn seek(
+    State(api): State<Api>,
+    Path(id): Path<String>,
+    Query(query): Query<HashMap<String, String>>,
+) -> ApiResult<Json<CommandOutcome>> {
+    let offset: SeekOffset = parameter(&query, "offset")?;
+    let session = api.session(&id).await?;
+    outcome(control::seek(session.as_ref(), offset).await)
+}
+
+async fn position(
+    State(api): State<Api>,
+    Path(id): Path<String>,
+    Query(query): Query<HashMap<String, String>>,
+) -> ApiResult<Json<CommandOutcome>> {
+    let position = duration::parse(required(&query, "position")?).map_err(ApiError::bad_request)?;
+    let session = api.session(&id).await?;
+    outcome(control::set_position(session.as_ref(), position).await)
+}
+
+async fn repeat(
+    State(api): State<Api>,
+    Path(id): Path<String>,
+    Query(query): Query<HashMap<String, String>>,
+) -> ApiResult<Json<CommandOutcome>> {
+    let mode: RepeatMode = parameter(&query, "mode")?;
+    let session = api.session(&id).await?;
+    outcome(control::set_repeat_mode(session.as_ref(), mode).await)
+}
+
+/// Without `state`, flips shuffle.
+async fn shuffle(
+    State(api): State<Api>,
+    Path(id): Path<String>,
+    Query(query): Query<HashMap<String, String>>,
+) -> ApiResult<Json<CommandOutcome>> {
+    let state = match query.get("state") {
+        Some(state) => Some(control::parse_switch(state).map_err(ApiError::bad_request)?),
+        None => None,
+    };
+    let session = api.session(&id).await?;
+    outcome(control::set_shuffle(session.as_ref(), state).await)
+}
+
+async fn rate(
+    State(api): State<Api>,
+    Path(id): Path<String>,
+    Query(query): Query<HashMap<String, String>>,
+) -> ApiResult<Json<CommandOutcome>> {
+    let rate: f64 = parameter(&query, "rate")?;
+    let session = api.session(&id).await?;
+    outcome(control::set_playback_rate(session.as_ref(), rate).await)
+}
+
+fn required<'a>(query: &'a HashMap<String, String>, name: &str) -> ApiResult<&'a str> {
+    query
+        .get(name)
+        .map(String::as_str)
+        .ok_or_else(|| ApiError::bad_request(anyhow!("missing query parameter {name:?}")))
+}
+
+fn parameter<T>(query: &HashMap<String, String>, name: &str) -> ApiResult<T>
+where
+    T: std::str::FromStr,
+    T::Err: std::fmt::Display,
+{
+    let value = required(query, name)?;
+    value
+        .parse()
+        .map_err(|e| ApiError::bad_request(anyhow!("invalid {name} {value:?}: {e}")))
+}
+
+/// The outcome as sent back to the client. A request the player declined is
+/// still a `200`; clients read `accepted` and `applied`.
+fn outcome(result: Result<CommandOutcome>) -> ApiResult<Json<CommandOutcome>> {
+    result.map(Json).map_err(ApiError::conflict)
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::body::{self, Body};
+    use axum::http::Method;
+    use serde_json::Value;
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::backend::mock::{MockBackend, MockSessionState, MockState};
+    use crate::snapshot::{MediaProperties, PlaybackInfo, PlaybackStatus, TimelineProperties};
+    use crate::thumbnail::ThumbnailData;
+
+    const PNG: &[u8] = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR";
+
+    fn backend() -> MockBackend {
+        let mut player = MockSessionState::new("org.mpris.MediaPlayer2.player");
+        player.is_current = true;
+        player.snapshot.media_properties = Some(MediaProperties {
+            title: Some("Song".to_string()),
+            ..Default::default()
+        });
+        player.snapshot.playback_info = Some(PlaybackInfo {
+            playback_status: Some(PlaybackStatus::Paused),
+            ..Default::default()
+        });
+        player.snapshot.timeline_properties = Some(TimelineProperties {
+            end_time: Some(Duration::from_secs(180)),
+            position: Some(Duration::from_secs(10)),
+            ..Default::default()
+        });
+        MockBackend::new(MockState {
+            sessions: vec![player, MockSessionState::new("bare")],
+        })
+    }
+
+    fn app(backend: &MockBackend) -> Router {
+        router(Api {
+            backend: Arc::new(backend.clone()),
+            current: None,
+            tick: Duration::ZERO,
+        })
+    }
+
+    async fn send(app: Router, method: Method, uri: &str, origin: Option<&str>) -> Response {
+        let mut request = axum::http::Request::builder().method(method).uri(uri);
+        if let Some(origin) = origin {
+            request = request.header(header::ORIGIN, origin);
+        }
+        app.oneshot(request.body(Body::empty()).unwrap())
+            .await
+            .unwrap()
+    }
+
+    async fn json(response: Response) -> Value {
+        let bytes = body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        serde_json::from_slice(&bytes).unwrap()
+    }
+
+    #[tokio::test]
+    async fn lists_and_reads_sessions() {
+        let backend = backend();
+        let response = send(app(&backend), Method::GET, "/sessions", None).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let list = json(response).await;
+        let ids = list["sessions"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|session| session["app_user_model_id"].as_str().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(ids, ["org.mpris.MediaPlayer2.player", "bare"]);
+
+        for uri in ["/sessions/current", "/sessions/player"] {
+            let response = send(app(&backend), Method::GET, uri, None).await;
+            assert_eq!(response.status(), StatusCode::OK, "{uri}");
+            let snapshot = json(response).await;
+            assert_eq!(snapshot["media_properties"]["title"], "Song", "{uri}");
+        }
+
+        let response = send(app(&backend), Method::GET, "/sessions/missing", None).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        let error = json(response).await;
+        assert!(error["error"].as_str().unwrap().contains("no session"));
+    }
+
+    #[tokio::test]
+    async fn serves_thumbnails() {
+        let backend = backend();
+        let thumbnail = ThumbnailData {
+            content_type: None,
+            bytes: PNG.to_vec(),
+        };
+        backend
+            .set_thumbnail("org.mpris.MediaPlayer2.player", Some(thumbnail))
+            .unwrap();
+
+        let response = send(
+            app(&backend),
+            Method::GET,
+            "/sessions/current/thumbnail",
+            None,
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "image/png");
+        let bytes = body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(bytes, PNG);
+
+        let response = send(app(&backend), Method::GET, "/sessions/bare/thumbnail", None).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn forwards_control_requests() {
+        let backend = backend();
+        let response = send(app(&backend), Method::POST, "/sessions/current/play", None).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let outcome = json(response).await;
+        assert_eq!(outcome["accepted"], true);
+
+        let uri = "/sessions/current/position?position=1:30";
+        let response = send(app(&backend), Method::POST, uri, None).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let uri = "/sessions/current/rate?rate=1.5";
+        let response = send(app(&backend), Method::POST, uri, None).await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let calls = backend.calls("org.mpris.MediaPlayer2.player");
+        assert_eq!(calls, ["play", "position 01:30.000", "rate 1.5"]);
+    }
+
+    #[tokio::test]
+    async fn maps_errors_to_statuses() {
+        let backend = backend();
+        for (uri, status) in [
+            ("/sessions/current/seek", StatusCode::BAD_REQUEST),
+            ("/sessions/current/rate?rate=fast", StatusCode::BAD_REQUEST),
+            ("/sessions/missing/play", StatusCode::NOT_FOUND),
+            ("/sessions/bare/seek?offset=10s", StatusCode::CONFLICT),
+            ("/nowhere", StatusCode::NOT_FOUND),
+        ] {
+            let response = send(app(&backend), Method::POST, uri, None).await;
+            assert_eq!(response.status(), status, "{uri}");
+            assert!(json(response).await["error"].is_string(), "{uri}");
+        }
+        assert!(backend.calls("org.mpris.MediaPlayer2.player").is_empty());
+    }
+
+    #[tokio::test]
+    async fn refuses_control_from_other_sites() {
+        let backend = backend();
+        for origin in [
+            "https://example.com",
+            "http://127.0.0.1.example.com",
+            "null",
+        ] {
+            let response = send(
+                app(&backend),
+                Method::POST,
+                "/sessions/current/pause",
+                Some(origin),
+            )
+            .await;
+            assert_eq!(response.status(), StatusCode::FORBIDDEN, "{origin}");
+        }
+        assert!(backend.calls("org.mpris.MediaPlayer2.player").is_empty());
+
+        for origin in [
+            "http://localhost:8080",
+            "http://127.0.0.1:7878",
+            "http://[::1]",
+        ] {
+            let response = send(
+                app(&backend),
+                Method::POST,
+                "/sessions/current/pause",
+                Some(origin),
+            )
+            .await;
+            assert_eq!(response.status(), StatusCode::OK, "{origin}");
+        }
+    }
+}
@@ -0,0 +1,588 @@
+//! Owned copies of everything a media session reports.
+//!
+//! Fields are `None` when the platform does not provide the value. Durations
+//! serialize as whole nanoseconds, matching the text output.
+
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+
+use anyhow::Result;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::backend::{MediaBackend, MediaSession};
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SessionSnapshot {
+    pub app_user_model_id: String,
+    pub media_properties: Option<MediaProperties>,
+    pub playback_info: Option<PlaybackInfo>,
+    pub timeline_properties: Option<TimelineProperties>,
+    /// Why values are missing, one entry per field that failed to read.
+    #[serde(default)]
+    pub errors: Vec<FieldError>,
+}
+
+impl SessionSnapshot {
+    /// Reads every field of `session`; a field that cannot be read is left
+    /// empty and its error recorded.
+    pub async fn collect(session: &dyn MediaSession) -> Self {
+        let mut media_errors = FieldErrors::new("media_properties");
+        let media_properties = MediaProperties::collect(session, &mut media_errors).await;
+        let mut playback_errors = FieldErrors::new("playback_info");
+        let playback_info = session.playback_info(&mut playback_errors).await;
+        let mut timeline_errors = FieldErrors::new("timeline_properties");
+        let timeline_properties = session.timeline_properties(&mut timeline_errors).await;
+
+        Self {
+            app_user_model_id: session.id().to_string(),
+            media_properties,
+            playback_info,
+            timeline_properties,
+            errors: [media_errors, playback_errors, timeline_errors]
+                .into_iter()
+                .flat_map(FieldErrors::into_vec)
+                .collect(),
+        }
+    }
+}
+
+/// A value the platform failed to provide.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FieldError {
+    /// Dotted path of the value, such as `media_properties.thumbnail`.
+    pub field: String,
+    /// HRESULT (`0x80004003`) or D-Bus error name, when the platform gave one.
+    pub code: Option<String>,
+    pub message: String,
+}
+
+impl FieldError {
+    /// Whether the field is `section` itself or one of its values.
+    pub fn belongs_to(&self, section: &str) -> bool {
+        self.field
+            .strip_prefix(section)
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with('.'))
+    }
+}
+
+/// An error raised by a platform API.
+pub trait PlatformError: fmt::Display {
+    /// HRESULT or D-Bus error name identifying the failure.
+    fn code(&self) -> Option<String> {
+        None
+    }
+
+    /// Description of the failure, without the code.
+    fn message(&self) -> String {
+        self.to_string()
+    }
+}
+
+/// Errors wrapping a [`FieldError`], such as those a mock session replays,
+/// keep its code and message.
+impl PlatformError for anyhow::Error {
+    fn code(&self) -> Option<String> {
+        self.downcast_ref::<FieldError>()
+            .and_then(|error| error.code.clone())
+    }
+
+    fn message(&self) -> String {
+        match self.downcast_ref::<FieldError>() {
+            Some(error) => error.message.clone(),
+            None => self.to_string(),
+        }
+    }
+}
+
+impl PlatformError for FieldError {
+    fn code(&self) -> Option<String> {
+        self.code.clone()
+    }
+
+    fn message(&self) -> String {
+        self.message.clone()
+    }
+}
+
+impl fmt::Display for FieldError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.code {
+            Some(code) => write!(f, "{} ({code}): {}", self.field, self.message),
+            None => write!(f, "{}: {}", self.field, self.message),
+        }
+    }
+}
+
+impl std::error::Error for FieldError {}
+
+/// Collects the [`FieldError`]s of one snapshot section.
+#[derive(Debug)]
+pub struct FieldErrors {
+    section: &'static str,
+    errors: Vec<FieldError>,
+}
+
+impl FieldErrors {
+    pub fn new(section: &'static str) -> Self {
+        Self {
+            section,
+            errors: vec![],
+        }
+    }
+
+    /// Returns the value, or records why `field` of this section is missing.
+    pub fn read<T>(&mut self, field: &str, result: Result<T, impl PlatformError>) -> Option<T> {
+        let field = format!("{}.{field}", self.section);
+        self.record(field, result)
+    }
+
+    /// Records the errors of `recorded` that belong to this section again,
+    /// as when replaying an earlier snapshot.
+    pub fn replay(&mut self, recorded: &[FieldError]) {
+        self.errors.extend(
+            recorded
+                .iter()
+                .filter(|error| error.belongs_to(self.section))
+                .cloned(),
+        );
+    }
+
+    /// Like [`Self::read`], for a failure that leaves the whole section unreadable.
+    pub fn section<T>(&mut self, result: Result<T, impl PlatformError>) -> Option<T> {
+        self.record(self.section.to_string(), result)
+    }
+
+    fn record<T>(&mut self, field: String, result: Result<T, impl PlatformError>) -> Option<T> {
+        match result {
+            Ok(value) => Some(value),
+            Err(error) => {
+                self.errors.push(FieldError {
+                    field,
+                    code: error.code(),
+                    message: error.message(),
+                });
+                None
+            }
+        }
+    }
+
+    /// Turns a section read into an error naming every field that failed.
+    pub fn require<T>(self, value: Option<T>) -> Result<T> {
+        match value {
+            Some(value) => Ok(value),
+            None => anyhow::bail!(
+                "could not read {}: {}",
+                self.section,
+                self.errors
+                    .iter()
+                    .map(|error| format!("{}: {}", error.field, error.message))
+                    .collect::<Vec<_>>()
+                    .join("; ")
+            ),
+        }
+    }
+
+    pub fn into_vec(self) -> Vec<FieldError> {
+        self.errors
+    }
+}
+
+/// Snapshots of every session a backend knows about.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SessionList {
+    pub sessions: Vec<ListedSession>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ListedSession {
+    /// Whether the backend reports this session as the current one.
+    pub is_current: bool,
+    #[serde(flatten)]
+    pub snapshot: SessionSnapshot,
+}
+
+impl SessionList {
+    pub async fn collect(backend: &dyn MediaBackend) -> Result<Self> {
+        let current_id = backend
+            .current_session()
+            .await?
+            .map(|session| session.id().to_string());
+
+        let mut sessions = vec![];
+        for session in backend.sessions().await? {
+            sessions.push(ListedSession {
+                is_current: current_id.as_deref() == Some(session.id()),
+                snapshot: SessionSnapshot::collect(session.as_ref()).await,
+            });
+        }
+        Ok(Self { sessions })
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MediaProperties {
+    pub album_artist: Option<String>,
+    pub album_title: Option<String>,
+    pub album_track_count: Option<i32>,
+    pub artist: Option<String>,
+    #[serde(default)]
+    pub genres: Vec<String>,
+    pub playback_type: Option<PlaybackType>,
+    pub subtitle: Option<String>,
+    pub thumbnail: Option<Thumbnail>,
+    pub title: Option<String>,
+    pub track_number: Option<i32>,
+}
+
+impl MediaProperties {
+    /// Reads the media properties of `session` and inspects the bytes of its
+    /// thumbnail.
+    pub async fn collect(session: &dyn MediaSession, errors: &mut FieldErrors) -> Option<Self> {
+        let mut media_properties = session.media_properties(errors).await?;
+        if let Some(thumbnail) = &mut media_properties.thumbnail {
+            if let Some(Some(data)) = errors.read("thumbnail.data", session.thumbnail().await) {
+                data.inspect().apply(thumbnail);
+            }
+        }
+        Some(media_properties)
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Thumbnail {
+    pub content_type: Option<String>,
+    pub size: Option<u64>,
+    /// Where the image lives, for platforms that reference it by URL.
+    pub url: Option<String>,
+    /// Format recognised from the image bytes; `content_type` is often
+    /// missing or wrong.
+    pub detected_content_type: Option<String>,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    /// Hex SHA-256 of the image bytes, to tell when the cover art changes.
+    pub sha256: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PlaybackInfo {
+    pub auto_repeat_mode: Option<RepeatMode>,
+    pub controls: Option<PlaybackControls>,
+    pub is_shuffle_active: Option<bool>,
+    pub playback_rate: Option<f64>,
+    pub playback_status: Option<PlaybackStatus>,
+    pub playback_type: Option<PlaybackType>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PlaybackControls {
+    pub is_channel_down_enabled: Option<bool>,
+    pub is_channel_up_enabled: Option<bool>,
+    pub is_fast_forward_enabled: Option<bool>,
+    pub is_next_enabled: Option<bool>,
+    pub is_pause_enabled: Option<bool>,
+    pub is_playback_position_enabled: Option<bool>,
+    pub is_playback_rate_enabled: Option<bool>,
+    pub is_play_enabled: Option<bool>,
+    pub is_play_pause_toggle_enabled: Option<bool>,
+    pub is_previous_enabled: Option<bool>,
+    pub is_record_enabled: Option<bool>,
+    pub is_repeat_enabled: Option<bool>,
+    pub is_rewind_enabled: Option<bool>,
+    pub is_shuffle_enabled: Option<bool>,
+    pub is_stop_enabled: Option<bool>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TimelineProperties {
+    #[serde(default, with = "option_nanos")]
+    pub start_time: Option<Duration>,
+    #[serde(default, with = "option_nanos")]
+    pub end_time: Option<Duration>,
+    #[serde(default, with = "option_nanos")]
+    pub max_seek_time: Option<Duration>,
+    #[serde(default, with = "option_nanos")]
+    pub min_seek_time: Option<Duration>,
+    #[serde(default, with = "option_nanos")]
+    pub position: Option<Duration>,
+    /// 100ns ticks since 1601-01-01 UTC, as in Windows `DateTime::UniversalTime`.
+    pub last_updated_time: Option<i64>,
+}
+
+/// Defines an enum mirroring a GSMTC integer enum. Values the platform may
+/// add later land in `Unknown` instead of being rejected. Variants display
+/// and serialize by name, or by the name given as `Variant("name")`, and
+/// `Unknown(n)` as `Unknown(n)`; deserializing also accepts the raw integer.
+macro_rules! platform_enum {
+    (
+        $(#[$meta:meta])*
+        pub enum $name:ident {
+            $($variant:ident $(($display:literal))? = $value:literal,)*
+        }
+    ) => {
+        $(#[$meta])*
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        pub enum $name {
+            $($variant,)*
+            Unknown(i32),
+        }
+
+        impl From<i32> for $name {
+            fn from(value: i32) -> Self {
+                match value {
+                    $($value => Self::$variant,)*
+                    value => Self::Unknown(value),
+                }
+            }
+        }
+
+        impl From<$name> for i32 {
+            fn from(value: $name) -> Self {
+                match value {
+                    $($name::$variant => $value,)*
+                    $name::Unknown(value) => value,
+                }
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                match self {
+                    $(Self::$variant => f.write_str(variant_name!($variant $(, $display)?)),)*
+                    Self::Unknown(value) => write!(f, "Unknown({value})"),
+                }
+            }
+        }
+
+        impl FromStr for $name {
+            type Err = anyhow::Error;
+
+            fn from_str(s: &str) -> Result<Self> {
+                $(if s.eq_ignore_ascii_case(variant_name!($variant $(, $display)?)) {
+                    return Ok(Self::$variant);
+                })*
+                let unknown = s
+                    .strip_prefix("Unknown(")
+                    .and_then(|s| s.strip_suffix(')'))
+                    .unwrap_or(s);
+                match unknown.parse::<i32>() {
+                    Ok(value) => Ok(Self::from(value)),
+                    Err(_) => anyhow::bail!(
+                        "unknown {} {s:?}, expected one of: {}",
+                        stringify!($name),
+                        [$(variant_name!($variant $(, $display)?)),*].join(", ")
+                    ),
+                }
+            }
+        }
+
+        impl Serialize for $name {
+            fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+                s.collect_str(self)
+            }
+        }
+
+        impl<'de> Deserialize<'de> for $name {
+            fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+                #[derive(Deserialize)]
+                #[serde(untagged)]
+                enum Repr {
+                    Name(String),
+                    Value(i32),
+                }
+                match Repr::deserialize(d)? {
+                    Repr::Name(name) => name.parse().map_err(serde::de::Error::custom),
+                    Repr::Value(value) => Ok(Self::from(value)),
+                }
+            }
+        }
+    };
+}
+
+/// The name a [`platform_enum!`] variant displays as.
+macro_rules! variant_name {
+    ($variant:ident) => {
+        stringify!($variant)
+    };
+    ($variant:ident, $display:literal) => {
+        $display
+    };
+}
+
+platform_enum! {
+    /// `GlobalSystemMediaTransportControlsSessionPlaybackStatus`.
+    pub enum PlaybackStatus {
+        Closed = 0,
+        Opened = 1,
+        Changing = 2,
+        Stopped = 3,
+        Playing = 4,
+        Paused = 5,
+    }
+}
+
+platform_enum! {
+    /// `MediaPlaybackAutoRepeatMode`.
+    pub enum RepeatMode {
+        None = 0,
+        Track = 1,
+        List = 2,
+    }
+}
+
+platform_enum! {
+    /// `MediaPlaybackType`. The platform's own "unknown" type is
+    /// `Unspecified`, which prints as `Unknown` as the platform names it.
+    pub enum PlaybackType {
+        Unspecified("Unknown") = 0,
+        Music = 1,
+        Video = 2,
+        Image = 3,
+    }
+}
+
+impl PlaybackStatus {
+    /// Parses an MPRIS `PlaybackStatus`, failing on values outside the spec.
+    pub fn from_mpris(status: &str) -> Result<Self> {
+        match status {
+            "Playing" => Ok(Self::Playing),
+            "Paused" => Ok(Self::Paused),
+            "Stopped" => Ok(Self::Stopped),
+            _ => anyhow::bail!("{status:?} is not an MPRIS PlaybackStatus"),
+        }
+    }
+
+    /// The MPRIS `PlaybackStatus` closest to this one; anything neither
+    /// playing nor paused counts as stopped.
+    pub fn to_mpris(self) -> &'static str {
+        match self {
+            Self::Playing => "Playing",
+            Self::Paused => "Paused",
+            _ => "Stopped",
+        }
+    }
+}
+
+impl RepeatMode {
+    /// Parses an MPRIS `LoopStatus`, failing on values outside the spec.
+    pub fn from_mpris(status: &str) -> Result<Self> {
+        match status {
+            "None" => Ok(Self::None),
+            "Track" => Ok(Self::Track),
+            "Playlist" => Ok(Self::List),
+            _ => anyhow::bail!("{status:?} is not an MPRIS LoopStatus"),
+        }
+    }
+
+    /// The MPRIS `LoopStatus` for this mode; `Unknown` values have none.
+    pub fn to_mpris(self) -> Option<&'static str> {
+        match self {
+            Self::None => Some("None"),
+            Self::Track => Some("Track"),
+            Self::List => Some("Playlist"),
+            Self::Unknown(_) => None,
+        }
+    }
+}
+
+pub(crate) mod nanos {
+    use std::time::Duration;
+
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S: Serializer>(value: &Duration, s: S) -> Result<S::Ok, S::Error> {
+        u64::try_from(value.as_nanos())
+            .unwrap_or(u64::MAX)
+            .serialize(s)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Duration, D::Error> {
+        Ok(Duration::from_nanos(u64::deserialize(d)?))
+    }
+}
+
+pub(crate) mod option_nanos {
+    use std::time::Duration;
+
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S: Serializer>(value: &Option<Duration>, s: S) -> Result<S::Ok, S::Error> {
+        value
+            .map(|value| u64::try_from(value.as_nanos()).unwrap_or(u64::MAX))
+            .serialize(s)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Option<Duration>, D::Error> {
+        Ok(Option::<u64>::deserialize(d)?.map(Duration::from_nanos))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn platform_unknown_prints_by_name() {
+        let unspecified = PlaybackType::from(0);
+        assert_eq!(unspecified, PlaybackType::Unspecified);
+        assert_eq!(unspecified.to_string(), "Unknown");
+        assert_eq!(serde_json::to_string(&unspecified).unwrap(), "\"Unknown\"");
+        assert_eq!(
+            "unknown".parse::<PlaybackType>().unwrap(),
+            PlaybackType::Unspecified
+        );
+        assert_eq!(i32::from(unspecified), 0);
+    }
+
+    #[test]
+    fn unknown_values_round_trip() {
+        let unknown = PlaybackStatus::Unknown(7);
+        assert_eq!(unknown.to_string(), "Unknown(7)");
+        let json = serde_json::to_string(&unknown).unwrap();
+        assert_eq!(json, "\"Unknown(7)\"");
+        assert_eq!(
+            serde_json::from_str::<PlaybackStatus>(&json).unwrap(),
+            unknown
+        );
+        let yaml = serde_yaml::to_string(&unknown).unwrap();
+        assert_eq!(
+            serde_yaml::from_str::<PlaybackStatus>(&yaml).unwrap(),
+            unknown
+        );
+        assert_eq!("Unknown(7)".parse::<PlaybackStatus>().unwrap(), unknown);
+    }
+
+    #[test]
+    fn parses_names_and_raw_integers() {
+        assert_eq!(
+            "playing".parse::<PlaybackStatus>().unwrap(),
+            PlaybackStatus::Playing
+        );
+        assert_eq!("2".parse::<RepeatMode>().unwrap(), RepeatMode::List);
+        assert_eq!("9".parse::<RepeatMode>().unwrap(), RepeatMode::Unknown(9));
+        assert_eq!(
+            serde_json::from_str::<PlaybackStatus>("4").unwrap(),
+            PlaybackStatus::Playing
+        );
+        assert_eq!(
+            serde_json::from_str::<PlaybackStatus>("42").unwrap(),
+            PlaybackStatus::Unknown(42)
+        );
+
+        let error = "bogus".parse::<RepeatMode>().unwrap_err().to_string();
+        assert!(
+            error.contains("expected one of: None, Track, List"),
+            "{error}"
+        );
+    }
+
+    #[test]
+    fn rejects_values_outside_the_mpris_spec() {
+        assert_eq!(
+            RepeatMode::from_mpris("Playlist").unwrap(),
+            RepeatMode::List
+        );
+        let error = PlaybackStatus::from_mpris("Buffering").unwrap_err();
+        assert!(error.to_string().contains("\"Buffering\""), "{error}");
+    }
+}
@@ -0,0 +1,81 @@
+//! A private session bus for tests talking D-Bus.
+
+use std::io::{BufRead, BufReader};
+use std::process::{Child, Command, Stdio};
+use std::time::Duration;
+
+use tokio::sync::mpsc::UnboundedReceiver;
+use zbus::{connection, Connection};
+
+use crate::backend::MediaEvent;
+
+/// A `dbus-daemon` of its own, stopped when dropped.
+pub struct PrivateBus {
+    daemon: Child,
+    address: String,
+}
+
+impl PrivateBus {
+    /// Starts a daemon, or returns `None` when `dbus-daemon` is not
+    /// installed so that the calling test can skip.
+    pub fn start() -> Option<Self> {
+        let mut daemon = match Command::new("dbus-daemon")
+            .args(["--session", "--nofork", "--print-address=1"])
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+        {
+            Ok(daemon) => daemon,
+            Err(error) => {
+                eprintln!("skipping: cannot start dbus-daemon: {error}");
+                return None;
+            }
+        };
+        let stdout = daemon.stdout.take().expect("stdout is piped");
+        let mut address = String::new();
+        BufReader::new(stdout)
+            .read_line(&mut address)
+            .expect("dbus-daemon prints its address");
+        Some(Self {
+            daemon,
+            address: address.trim().to_string(),
+        })
+    }
+
+    /// A builder for another connection to the bus.
+    pub fn builder(&self) -> connection::Builder<'static> {
+        connection::Builder::address(self.address.as_str()).expect("valid bus address")
+    }
+
+    pub async fn connect(&self) -> Connection {
+        self.builder()
+            .build()
+            .await
+            .expect("bus accepts connections")
+    }
+}
+
+impl Drop for PrivateBus {
+    fn drop(&mut self) {
+        let _ = self.daemon.kill();
+        let _ = self.daemon.wait();
+    }
+}
+
+/// Waits for the first event `wanted` accepts, skipping the others.
+pub async fn wait_for(
+    events: &mut UnboundedReceiver<MediaEvent>,
+    wanted: impl Fn(&MediaEvent) -> bool,
+) -> MediaEvent {
+    let wait = async {
+        loop {
+            let event = events.recv().await.expect("watch ended");
+            if wanted(&event) {
+                return event;
+            }
+        }
+    };
+    tokio::time::timeout(Duration::from_secs(5), wait)
+        .await
+        .expect("event did not arrive")
+}
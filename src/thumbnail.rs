@@ -0,0 +1,206 @@
+//! Cover art bytes and saving them to disk.
+
+use std::fmt::Write;
+use std::fs;
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use image::ImageReader;
+use sha2::{Digest, Sha256};
+
+use crate::snapshot::Thumbnail;
+
+/// The image behind a session's thumbnail.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ThumbnailData {
+    /// Content type the platform reported, if any.
+    pub content_type: Option<String>,
+    pub bytes: Vec<u8>,
+}
+
+impl ThumbnailData {
+    /// File extension for the image, from its magic bytes or, failing that,
+    /// from the reported content type.
+    pub fn extension(&self) -> Option<&'static str> {
+        sniff_content_type(&self.bytes)
+            .or(self.content_type.as_deref())
+            .and_then(extension_for_content_type)
+    }
+
+    /// Looks at the bytes themselves: their real format, the image size and
+    /// a hash that changes exactly when the cover art does.
+    pub fn inspect(&self) -> Inspection {
+        let (width, height) = ImageReader::new(Cursor::new(&self.bytes))
+            .with_guessed_format()
+            .ok()
+            .and_then(|reader| reader.into_dimensions().ok())
+            .unzip();
+
+        let mut sha256 = String::with_capacity(64);
+        for byte in Sha256::digest(&self.bytes) {
+            let _ = write!(sha256, "{byte:02x}");
+        }
+
+        Inspection {
+            content_type: sniff_content_type(&self.bytes),
+            width,
+            height,
+            sha256,
+        }
+    }
+
+    /// Writes the image to `path`. A directory gets a `thumbnail.<ext>` file,
+    /// and a path without an extension gets the image's one appended.
+    pub fn save(&self, path: &Path) -> Result<PathBuf> {
+        let extension = self.extension().unwrap_or("bin");
+        let path = if path.is_dir() {
+            path.join(format!("thumbnail.{extension}"))
+        } else if path.extension().is_none() {
+            path.with_extension(extension)
+        } else {
+            path.to_path_buf()
+        };
+        fs::write(&path, &self.bytes)
+            .with_context(|| format!("could not write {}", path.display()))?;
+        Ok(path)
+    }
+}
+
+/// What [`ThumbnailData::inspect`] found out.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Inspection {
+    /// Format recognised from the magic bytes.
+    pub content_type: Option<&'static str>,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    /// Hex SHA-256 of the image bytes.
+    pub sha256: String,
+}
+
+impl Inspection {
+    /// Records the findings on the snapshot's thumbnail.
+    pub fn apply(self, thumbnail: &mut Thumbnail) {
+        thumbnail.detected_content_type = self.content_type.map(str::to_string);
+        thumbnail.width = self.width;
+        thumbnail.height = self.height;
+        thumbnail.sha256 = Some(self.sha256);
+    }
+}
+
+/// Maps an image content type to its usual file extension.
+pub fn extension_for_content_type(content_type: &str) -> Option<&'static str> {
+    let essence = content_type.split(';').next().unwrap_or_default().trim();
+    match essence.to_ascii_lowercase().as_str() {
+        "image/png" => Some("png"),
+        "image/jpeg" | "image/jpg" => Some("jpg"),
+        "image/webp" => Some("webp"),
+        "image/gif" => Some("gif"),
+        "image/bmp" => Some("bmp"),
+        _ => None,
+    }
+}
+
+/// Recognises the common cover art formats by their magic bytes.
+pub fn sniff_content_type(bytes: &[u8]) -> Option<&'static str> {
+    match bytes {
+        [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n', ..] => Some("image/png"),
+        [0xff, 0xd8, 0xff, ..] => Some("image/jpeg"),
+        [b'R', b'I', b'F', b'F', _, _, _, _, b'W', b'E', b'B', b'P', ..] => Some("image/webp"),
+        [b'G', b'I', b'F', b'8', b'7' | b'9', b'a', ..] => Some("image/gif"),
+        [b'B', b'M', ..] => Some("image/bmp"),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{ImageFormat, RgbImage};
+
+    use super::*;
+
+    fn png(width: u32, height: u32) -> Vec<u8> {
+        let mut bytes = Cursor::new(vec![]);
+        RgbImage::new(width, height)
+            .write_to(&mut bytes, ImageFormat::Png)
+            .unwrap();
+        bytes.into_inner()
+    }
+
+    fn data(content_type: Option<&str>, bytes: &[u8]) -> ThumbnailData {
+        ThumbnailData {
+            content_type: content_type.map(str::to_string),
+            bytes: bytes.to_vec(),
+        }
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("test-gsmtc-{name}-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn sniffs_each_format() {
+        for (bytes, content_type) in [
+            (&b"\x89PNG\r\n\x1a\n\0\0"[..], "image/png"),
+            (b"\xff\xd8\xff\xe0", "image/jpeg"),
+            (b"RIFF\0\0\0\0WEBPVP8 ", "image/webp"),
+            (b"GIF87a", "image/gif"),
+            (b"GIF89a", "image/gif"),
+            (b"BM\0\0", "image/bmp"),
+        ] {
+            assert_eq!(sniff_content_type(bytes), Some(content_type), "{bytes:?}");
+        }
+        assert_eq!(sniff_content_type(b"RIFF\0\0\0\0WAVE"), None);
+        assert_eq!(sniff_content_type(b"<svg"), None);
+        assert_eq!(sniff_content_type(b""), None);
+    }
+
+    #[test]
+    fn inspects_size_and_hash() {
+        let inspection = data(None, &png(3, 2)).inspect();
+        assert_eq!(inspection.content_type, Some("image/png"));
+        assert_eq!((inspection.width, inspection.height), (Some(3), Some(2)));
+        assert_eq!(inspection.sha256.len(), 64);
+        assert_ne!(inspection.sha256, data(None, &png(2, 3)).inspect().sha256);
+
+        let empty = data(Some("image/png"), b"").inspect();
+        assert_eq!(empty.content_type, None);
+        assert_eq!((empty.width, empty.height), (None, None));
+        assert_eq!(
+            empty.sha256,
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+    }
+
+    #[test]
+    fn prefers_the_sniffed_extension() {
+        assert_eq!(
+            data(Some("image/jpeg"), &png(1, 1)).extension(),
+            Some("png")
+        );
+        assert_eq!(data(Some("image/JPEG; q=1"), b"").extension(), Some("jpg"));
+        assert_eq!(data(Some("text/plain"), b"").extension(), None);
+        assert_eq!(data(None, b"").extension(), None);
+    }
+
+    #[test]
+    fn saves_into_directories_and_adds_extensions() {
+        let dir = temp_dir("thumbnail");
+        let image = data(None, &png(1, 1));
+
+        let in_dir = image.save(&dir).unwrap();
+        let without_extension = image.save(&dir.join("cover")).unwrap();
+        let with_extension = image.save(&dir.join("cover.jpeg")).unwrap();
+        let unknown = data(None, b"\0").save(&dir.join("blob")).unwrap();
+        let saved = fs::read(&with_extension).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(in_dir, dir.join("thumbnail.png"));
+        assert_eq!(without_extension, dir.join("cover.png"));
+        assert_eq!(with_extension, dir.join("cover.jpeg"));
+        assert_eq!(unknown, dir.join("blob.bin"));
+        assert_eq!(saved, image.bytes);
+    }
+}
@@ -0,0 +1,185 @@
+//! Session traces: the state of every session when recording started,
+//! followed by each event as [`crate::events::EventRecord`]s.
+//!
+//! A trace is NDJSON. The first line is a [`TraceHeader`]; every other line
+//! is exactly what `watch --format ndjson` prints, so such output can be
+//! replayed as well, starting from no sessions.
+
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::time::{Instant, SystemTime};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::backend::mock::{MockSessionState, MockState};
+use crate::backend::{universal_time, MediaBackend};
+use crate::events::EventRecord;
+use crate::format;
+use crate::snapshot::SessionList;
+
+/// First line of a trace.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TraceHeader {
+    /// When recording started, in 100ns ticks since 1601-01-01 UTC; event
+    /// timestamps count from here.
+    pub recorded_at: i64,
+    /// Every session as it was when recording started.
+    #[serde(flatten)]
+    pub state: MockState,
+}
+
+/// A trace read back from a file.
+#[derive(Debug, Clone, Default)]
+pub struct Trace {
+    pub header: Option<TraceHeader>,
+    pub records: Vec<EventRecord>,
+}
+
+impl Trace {
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = fs::read_to_string(path)
+            .with_context(|| format!("cannot read trace {}", path.display()))?;
+        Self::parse(&text).with_context(|| format!("cannot parse trace {}", path.display()))
+    }
+
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut trace = Trace::default();
+        let lines = text
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty());
+        for (index, line) in lines {
+            let first = trace.header.is_none() && trace.records.is_empty();
+            if first && is_header(line) {
+                let header = serde_json::from_str(line)
+                    .with_context(|| format!("line {}: invalid trace header", index + 1))?;
+                trace.header = Some(header);
+                continue;
+            }
+            let record = serde_json::from_str(line)
+                .with_context(|| format!("line {}: invalid event record", index + 1))?;
+            trace.records.push(record);
+        }
+        Ok(trace)
+    }
+}
+
+/// Whether `line` is a header rather than an event record, which always
+/// carries a `kind`.
+fn is_header(line: &str) -> bool {
+    serde_json::from_str::<serde_json::Value>(line)
+        .is_ok_and(|value| value.get("kind").is_none() && value.get("sessions").is_some())
+}
+
+/// Writes the header and then every event of `backend` to `w`, flushing
+/// after each line, until the backend stops reporting events.
+pub async fn record(backend: &dyn MediaBackend, w: &mut impl Write) -> Result<()> {
+    let mut events = backend.watch().await?;
+    let started = Instant::now();
+    let header = TraceHeader {
+        recorded_at: universal_time(SystemTime::now()),
+        state: state_of(SessionList::collect(backend).await?),
+    };
+    format::write_ndjson(w, &header)?;
+    w.flush()?;
+
+    while let Some(event) = events.recv().await {
+        let record = EventRecord::collect(backend, event, started).await;
+        format::write_ndjson(w, &record)?;
+        w.flush()?;
+    }
+    Ok(())
+}
+
+fn state_of(list: SessionList) -> MockState {
+    MockState {
+        sessions: list
+            .sessions
+            .into_iter()
+            .map(|session| MockSessionState {
+                is_current: session.is_current,
+                snapshot: session.snapshot,
+                ..Default::default()
+            })
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::mock::MockBackend;
+    use crate::backend::replay::ReplayBackend;
+    use crate::snapshot::MediaProperties;
+
+    fn titled(id: &str, title: &str, is_current: bool) -> MockSessionState {
+        let mut session = MockSessionState::new(id);
+        session.is_current = is_current;
+        session.snapshot.media_properties = Some(MediaProperties {
+            title: Some(title.to_string()),
+            ..Default::default()
+        });
+        session
+    }
+
+    async fn listing(backend: &dyn MediaBackend) -> serde_json::Value {
+        serde_json::to_value(SessionList::collect(backend).await.unwrap()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn replays_what_was_recorded() {
+        let source = MockBackend::new(MockState {
+            sessions: vec![titled("player", "First", true)],
+        });
+        let before = listing(&source).await;
+
+        // The mock never waits, so recording has written the header and
+        // is waiting for events by the time the changes start.
+        let mut output = vec![];
+        let change = async {
+            source
+                .update("player", |snapshot| {
+                    snapshot.media_properties.as_mut().unwrap().title = Some("Second".to_string());
+                })
+                .unwrap();
+            source.add_session(titled("other", "Other", false));
+            source.set_current(Some("other")).unwrap();
+            source.remove_session("player").unwrap();
+            source.close();
+        };
+        let (result, ()) = tokio::join!(record(&source, &mut output), change);
+        result.unwrap();
+        let after = listing(&source).await;
+
+        let trace = Trace::parse(std::str::from_utf8(&output).unwrap()).unwrap();
+        let header = trace.header.as_ref().expect("trace starts with a header");
+        assert_eq!(header.state.sessions.len(), 1);
+        let recorded = trace
+            .records
+            .iter()
+            .map(|record| record.event.clone())
+            .collect::<Vec<_>>();
+        let names = recorded.iter().map(ToString::to_string).collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            [
+                "media_properties_changed \"player\"",
+                "session_added \"other\"",
+                "current_session_changed \"other\"",
+                "session_removed \"player\"",
+            ]
+        );
+
+        let replay = ReplayBackend::new(trace, 1000.0).unwrap();
+        assert_eq!(listing(&replay).await, before);
+        let mut events = replay.watch().await.unwrap();
+        let mut replayed = vec![];
+        while let Some(event) = events.recv().await {
+            replayed.push(event);
+        }
+        assert_eq!(replayed, recorded);
+        assert_eq!(listing(&replay).await, after);
+    }
+}